@@ -0,0 +1,151 @@
+use bobby_carrot::smaf;
+use sdl2::{
+    mixer::{self, Channel, Chunk, InitFlag, LoaderRWops, Music, Sdl2MixerContext},
+    rwops::RWops,
+    AudioSubsystem, Sdl,
+};
+
+/// Volume steps of the volume keys, out of 100.
+pub const VOLUME_STEP: u32 = 10;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Sound {
+    Carrot,
+    Lock,
+    Death,
+    Title,
+    Cleared,
+    End,
+}
+
+/// Sound effects and music. Without an audio device the game goes on
+/// silently, and without MIDI support it goes on without music.
+pub struct Audio {
+    sounds: Option<Sounds>,
+    /// In percent.
+    volume: u32,
+    muted: bool,
+}
+
+/// The fields drop in order, the audio is closed last.
+struct Sounds {
+    carrot: Chunk,
+    lock: Chunk,
+    death: Chunk,
+    music: Option<Tunes>,
+    _audio_subsystem: AudioSubsystem,
+}
+
+/// The MIDI music, the MIDI support of the mixer is dropped last.
+struct Tunes {
+    title: Music<'static>,
+    cleared: Music<'static>,
+    end: Music<'static>,
+    _mixer_context: Sdl2MixerContext,
+}
+
+impl Audio {
+    pub fn open(context: &Sdl, volume: u32) -> Audio {
+        let sounds = Sounds::load(context)
+            .map_err(|err| eprintln!("No audio: {err}"))
+            .ok();
+        let audio = Audio {
+            sounds,
+            volume: volume.min(100),
+            muted: false,
+        };
+        audio.apply_volume();
+        audio
+    }
+
+    pub fn play(&self, sound: Sound) {
+        let Some(sounds) = &self.sounds else {
+            return;
+        };
+        let music = sounds.music.as_ref();
+        // Running out of channels only loses an effect
+        let _ = match sound {
+            Sound::Carrot => Channel::all().play(&sounds.carrot, 0).map(|_| ()),
+            Sound::Lock => Channel::all().play(&sounds.lock, 0).map(|_| ()),
+            Sound::Death => Channel::all().play(&sounds.death, 0).map(|_| ()),
+            Sound::Title => music.map_or(Ok(()), |music| music.title.play(0)),
+            Sound::Cleared => music.map_or(Ok(()), |music| music.cleared.play(0)),
+            Sound::End => music.map_or(Ok(()), |music| music.end.play(0)),
+        };
+    }
+
+    pub fn stop_music(&self) {
+        if self
+            .sounds
+            .as_ref()
+            .is_some_and(|sounds| sounds.music.is_some())
+        {
+            Music::halt();
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.apply_volume();
+    }
+
+    /// Change the volume by `delta` percent.
+    pub fn change_volume(&mut self, delta: i32) {
+        self.volume = self.volume.saturating_add_signed(delta).min(100);
+        self.muted = false;
+        self.apply_volume();
+    }
+
+    fn apply_volume(&self) {
+        if self.sounds.is_none() {
+            return;
+        }
+        let volume = if self.muted {
+            0
+        } else {
+            (self.volume * mixer::MAX_VOLUME as u32 / 100) as i32
+        };
+        Channel::all().set_volume(volume);
+        Music::set_volume(volume);
+    }
+}
+
+impl Sounds {
+    fn load(context: &Sdl) -> Result<Sounds, Box<dyn std::error::Error>> {
+        let audio_subsystem = context.audio()?;
+        mixer::open_audio(
+            mixer::DEFAULT_FREQUENCY,
+            mixer::DEFAULT_FORMAT,
+            mixer::DEFAULT_CHANNELS,
+            1024,
+        )?;
+        mixer::allocate_channels(8);
+
+        let effect = |data: &[u8]| -> Result<Chunk, Box<dyn std::error::Error>> {
+            let wav = smaf::decode_wave(data)?.to_wav();
+            let chunk = RWops::from_bytes(&wav)?.load_wav()?;
+            Ok(chunk)
+        };
+        Ok(Sounds {
+            carrot: effect(include_bytes!("assets/audio/carrot.mmf"))?,
+            lock: effect(include_bytes!("assets/audio/lock.mmf"))?,
+            death: effect(include_bytes!("assets/audio/death.mmf"))?,
+            music: Tunes::load()
+                .map_err(|err| eprintln!("No music: {err}"))
+                .ok(),
+            _audio_subsystem: audio_subsystem,
+        })
+    }
+}
+
+impl Tunes {
+    fn load() -> Result<Tunes, Box<dyn std::error::Error>> {
+        let mixer_context = mixer::init(InitFlag::MID)?;
+        Ok(Tunes {
+            title: Music::from_static_bytes(include_bytes!("assets/audio/title.mid"))?,
+            cleared: Music::from_static_bytes(include_bytes!("assets/audio/cleared.mid"))?,
+            end: Music::from_static_bytes(include_bytes!("assets/audio/end.mid"))?,
+            _mixer_context: mixer_context,
+        })
+    }
+}
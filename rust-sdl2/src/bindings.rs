@@ -0,0 +1,294 @@
+//! Which keys do what while playing.
+//!
+//! The key file is a text file of `action = key` lines, lines starting with
+//! `#` are comments, a `#` after a key is the `#` key. An action listed several times has several keys, one
+//! listed without a key has none, and the actions left out keep their default
+//! keys. Keys are named the way SDL names them:
+//!
+//! ```text
+//! left = Left
+//! left = A
+//! restart = Backspace
+//! hints =
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use bobby_carrot::{config_dir, key_value, Direction};
+use sdl2::keyboard::{KeyboardState, Keycode, Scancode};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    Restart,
+    Undo,
+    /// Undo moves one after the other while held.
+    Rewind,
+    Next,
+    Previous,
+    FullView,
+    Fullscreen,
+    Help,
+    Hints,
+    /// Freeze the game, with a menu to resume it.
+    Pause,
+    Mute,
+    VolumeDown,
+    VolumeUp,
+    /// Back to the level-select menu.
+    Menu,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 19] = [
+        Action::Left,
+        Action::Right,
+        Action::Up,
+        Action::Down,
+        Action::Restart,
+        Action::Undo,
+        Action::Rewind,
+        Action::Next,
+        Action::Previous,
+        Action::FullView,
+        Action::Fullscreen,
+        Action::Help,
+        Action::Hints,
+        Action::Pause,
+        Action::Mute,
+        Action::VolumeDown,
+        Action::VolumeUp,
+        Action::Menu,
+        Action::Quit,
+    ];
+
+    /// The name of the action in the key file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Restart => "restart",
+            Action::Undo => "undo",
+            Action::Rewind => "rewind",
+            Action::Next => "next",
+            Action::Previous => "previous",
+            Action::FullView => "full_view",
+            Action::Fullscreen => "fullscreen",
+            Action::Help => "help",
+            Action::Hints => "hints",
+            Action::Pause => "pause",
+            Action::Mute => "mute",
+            Action::VolumeDown => "volume_down",
+            Action::VolumeUp => "volume_up",
+            Action::Menu => "menu",
+            Action::Quit => "quit",
+        }
+    }
+
+    fn default_keys(self) -> &'static [Keycode] {
+        match self {
+            Action::Left => &[Keycode::Left, Keycode::A],
+            Action::Right => &[Keycode::Right, Keycode::D],
+            Action::Up => &[Keycode::Up, Keycode::W],
+            Action::Down => &[Keycode::Down, Keycode::S],
+            Action::Restart => &[Keycode::R],
+            Action::Undo => &[Keycode::U],
+            Action::Rewind => &[Keycode::Backspace],
+            Action::Next => &[Keycode::N],
+            Action::Previous => &[Keycode::P],
+            Action::FullView => &[Keycode::F],
+            Action::Fullscreen => &[Keycode::F11],
+            Action::Help => &[Keycode::H, Keycode::F1],
+            Action::Hints => &[Keycode::I],
+            Action::Pause => &[Keycode::Escape, Keycode::Pause],
+            Action::Mute => &[Keycode::M],
+            Action::VolumeDown => &[Keycode::Minus, Keycode::KpMinus],
+            Action::VolumeUp => &[Keycode::Equals, Keycode::Plus, Keycode::KpPlus],
+            Action::Menu => &[Keycode::L],
+            Action::Quit => &[Keycode::Q],
+        }
+    }
+}
+
+/// The keys of each action, in the order of [`Action::ALL`].
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    keys: Vec<Vec<Keycode>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings {
+            keys: Action::ALL
+                .iter()
+                .map(|action| action.default_keys().to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// `keys.txt` in the [`config_dir`].
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("keys.txt"))
+    }
+
+    /// Read a key file, a missing one leaves the default keys.
+    pub fn load(path: &Path) -> Result<Bindings, Box<dyn std::error::Error>> {
+        match key_value::read_if_exists(path)? {
+            Some(content) => Ok(Bindings::parse(path, &content)?),
+            None => Ok(Bindings::default()),
+        }
+    }
+
+    /// Parse `content`, the text of the key file at `path`.
+    fn parse(path: &Path, content: &str) -> Result<Bindings, String> {
+        let mut bindings = Bindings::default();
+        let mut listed = Vec::new();
+        for line in key_value::lines_with_hashes(path, content) {
+            let (name, key) = line.key_value()?;
+            let action = Action::ALL
+                .into_iter()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| line.error(format!("Unknown action: {name}")))?;
+            // the keys in the file replace the default ones
+            if !listed.contains(&action) {
+                bindings.clear(action);
+                listed.push(action);
+            }
+            if !key.is_empty() {
+                let code = Keycode::from_name(key)
+                    .ok_or_else(|| line.error(format!("Unknown key: {key}")))?;
+                bindings.add(action, code);
+            }
+        }
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(key_value::write(path, &self.to_text())?)
+    }
+
+    /// The text of the key file.
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for action in Action::ALL {
+            let keys = self.keys(action);
+            if keys.is_empty() {
+                text.push_str(&format!("{} =\n", action.name()));
+            }
+            for code in keys {
+                text.push_str(&format!("{} = {}\n", action.name(), code.name()));
+            }
+        }
+        text
+    }
+
+    pub fn keys(&self, action: Action) -> &[Keycode] {
+        &self.keys[action as usize]
+    }
+
+    pub fn add(&mut self, action: Action, code: Keycode) {
+        let keys = &mut self.keys[action as usize];
+        if !keys.contains(&code) {
+            keys.push(code);
+        }
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.keys[action as usize].clear();
+    }
+
+    /// The action of a key, the first one of [`Action::ALL`] if the key is
+    /// bound to several.
+    pub fn action(&self, code: Keycode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| self.keys(*action).contains(&code))
+    }
+
+    /// Whether a key of the action is held down.
+    pub fn is_held(&self, action: Action, keyboard: &KeyboardState) -> bool {
+        self.keys(action).iter().any(|code| {
+            Scancode::from_keycode(*code).is_some_and(|code| keyboard.is_scancode_pressed(code))
+        })
+    }
+
+    /// The direction of the first of the held keys of [`Action::Left`],
+    /// [`Action::Right`], [`Action::Up`] and [`Action::Down`].
+    pub fn held_direction(&self, keyboard: &KeyboardState) -> Option<Direction> {
+        [
+            (Action::Left, Direction::Left),
+            (Action::Right, Direction::Right),
+            (Action::Up, Direction::Up),
+            (Action::Down, Direction::Down),
+        ]
+        .into_iter()
+        .find(|(action, _)| self.is_held(*action, keyboard))
+        .map(|(_, direction)| direction)
+    }
+
+    /// The keys bound to more than one action, with those actions.
+    pub fn conflicts(&self) -> Vec<(Keycode, Vec<Action>)> {
+        let mut conflicts: Vec<(Keycode, Vec<Action>)> = Vec::new();
+        for action in Action::ALL {
+            for code in self.keys(action) {
+                match conflicts.iter_mut().find(|(other, _)| other == code) {
+                    Some((_, actions)) => actions.push(action),
+                    None => conflicts.push((*code, vec![action])),
+                }
+            }
+        }
+        conflicts.retain(|(_, actions)| actions.len() > 1);
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Bindings, String> {
+        Bindings::parse(Path::new("keys.txt"), content)
+    }
+
+    #[test]
+    fn a_malformed_line_is_an_error() {
+        let err = parse("left Left\n").unwrap_err();
+        assert!(
+            err.starts_with("keys.txt:1: Expected `key = value`"),
+            "{err}"
+        );
+        assert_eq!(
+            parse("# arrows\nleft = Larrow\n").unwrap_err(),
+            "keys.txt:2: Unknown key: Larrow"
+        );
+    }
+
+    #[test]
+    fn an_unknown_action_is_an_error() {
+        assert_eq!(
+            parse("jump = Space\n").unwrap_err(),
+            "keys.txt:1: Unknown action: jump"
+        );
+    }
+
+    #[test]
+    fn saved_bindings_load_back() {
+        let mut bindings = Bindings::default();
+        bindings.clear(Action::Hints);
+        bindings.clear(Action::Left);
+        bindings.add(Action::Left, Keycode::Hash);
+        bindings.add(Action::Left, Keycode::J);
+        let loaded = parse(&bindings.to_text()).unwrap();
+        for action in Action::ALL {
+            assert_eq!(loaded.keys(action), bindings.keys(action), "{action:?}");
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::game::{Direction, GameEvent, KeyColor};
+use crate::grid::Grid;
+use crate::tile::{Collectible, SwitchColor, Tile};
+use crate::FRAMES_PER_STEP;
+
+#[derive(Debug, Clone)]
+pub struct Bobby {
+    pub state: State,
+    pub next_state: Option<State>,
+    pub start_frame: u32,
+    pub last_action_frame: u32,
+    pub coord_src: (u32, u32),
+    pub coord_dest: (u32, u32),
+    // hud
+    pub carrot_count: usize,
+    pub egg_count: usize,
+    pub key_gray: usize,
+    pub key_yellow: usize,
+    pub key_red: usize,
+    pub faded_out: bool,
+    pub dead: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum State {
+    Idle,
+    Death,
+    FadeIn,
+    FadeOut,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Bobby {
+    pub fn new(start_frame: u32, coord_src: (u32, u32)) -> Bobby {
+        Bobby {
+            state: State::FadeIn,
+            next_state: None,
+            start_frame,
+            last_action_frame: start_frame,
+            coord_src,
+            coord_dest: coord_src,
+            // hud
+            carrot_count: 0,
+            egg_count: 0,
+            key_gray: 0,
+            key_yellow: 0,
+            key_red: 0,
+            faded_out: false,
+            dead: false,
+        }
+    }
+
+    /// Advance the animation of the current state to `frame`, and apply the
+    /// effects of the tiles once a step is finished.
+    pub(crate) fn update(&mut self, frame: u32, map_data: &mut Grid, events: &mut Vec<GameEvent>) {
+        let delta_frame = frame - self.start_frame;
+        let is_walking = self.coord_src != self.coord_dest;
+        let step = delta_frame / FRAMES_PER_STEP;
+        match self.state {
+            State::Idle => return,
+            State::Death => {
+                if step / 3 >= 12 {
+                    self.dead = true;
+                }
+                return;
+            }
+            State::FadeIn => {
+                if step >= 8 {
+                    self.start_frame = frame;
+                    self.state = State::Down;
+                }
+                return;
+            }
+            State::FadeOut => {
+                if step >= 8 && !self.faded_out {
+                    self.faded_out = true;
+                    events.push(GameEvent::LevelCleared);
+                }
+                return;
+            }
+            State::Left => {
+                if is_walking {
+                    assert_eq!(self.coord_src.0, self.coord_dest.0 + 1);
+                    assert_eq!(self.coord_src.1, self.coord_dest.1);
+                }
+            }
+            State::Right => {
+                if is_walking {
+                    assert_eq!(self.coord_src.0 + 1, self.coord_dest.0);
+                    assert_eq!(self.coord_src.1, self.coord_dest.1);
+                }
+            }
+            State::Up => {
+                if is_walking {
+                    assert_eq!(self.coord_src.0, self.coord_dest.0);
+                    assert_eq!(self.coord_src.1, self.coord_dest.1 + 1);
+                }
+            }
+            State::Down => {
+                if is_walking {
+                    assert_eq!(self.coord_src.0, self.coord_dest.0);
+                    assert_eq!(self.coord_src.1 + 1, self.coord_dest.1);
+                }
+            }
+        }
+
+        if step == 6 && is_walking && self.next_state == Some(State::Death) {
+            self.start_frame = frame;
+            self.state = State::Death;
+            events.push(GameEvent::Died);
+        } else if step == 8 && is_walking {
+            let old_pos = self.coord_src;
+            let new_pos = self.coord_dest;
+            let old_tile = map_data[old_pos];
+            if old_tile.is_rail() {
+                map_data[old_pos] = old_tile.toggled();
+            } else if old_tile == Tile::Crumble {
+                map_data[old_pos] = Tile::Hole;
+                events.push(GameEvent::TileCrumbled(self.coord_src));
+            } else if old_tile.collectible() == Some(Collectible::Egg) {
+                map_data[old_pos] = old_tile.collected();
+                self.egg_count += 1;
+                events.push(GameEvent::EggCollected);
+            }
+
+            let new_tile = map_data[new_pos];
+            match new_tile.collectible() {
+                Some(Collectible::Carrot) => {
+                    map_data[new_pos] = new_tile.collected();
+                    self.carrot_count += 1;
+                    events.push(GameEvent::CarrotCollected);
+                }
+                Some(Collectible::Key(color)) => {
+                    map_data[new_pos] = new_tile.collected();
+                    *self.key_mut(color) += 1;
+                    events.push(GameEvent::KeyCollected(color));
+                }
+                // eggs are collected when leaving them
+                Some(Collectible::Egg) | None => {}
+            }
+            if let Some(color) = new_tile.lock() {
+                if self.key(color) > 0 {
+                    map_data[new_pos] = Tile::Floor;
+                    *self.key_mut(color) -= 1;
+                    events.push(GameEvent::LockOpened(color));
+                }
+            }
+            if let Some(color) = new_tile.switch() {
+                for tile in map_data.tiles_mut() {
+                    if tile.switch_group() == Some(color) {
+                        *tile = tile.toggled();
+                    }
+                }
+                events.push(match color {
+                    SwitchColor::Red => GameEvent::RedSwitchToggled,
+                    SwitchColor::Yellow => GameEvent::YellowSwitchToggled,
+                });
+            }
+            // flow
+            if let Some(direction) = new_tile.conveyor() {
+                self.next_state = Some(direction.into());
+            }
+
+            events.push(GameEvent::Moved {
+                from: self.coord_src,
+                to: self.coord_dest,
+            });
+            self.coord_src = self.coord_dest;
+            self.start_frame = frame;
+            if let Some(state) = self.next_state.take() {
+                self.update_state(state, frame, map_data);
+            }
+        }
+    }
+
+    pub fn is_walking(&self) -> bool {
+        self.coord_src != self.coord_dest
+    }
+
+    pub(crate) fn update_next_state(&mut self, state: State, frame: u32) {
+        if (frame - self.start_frame) / FRAMES_PER_STEP > 3
+            && self.next_state != Some(State::Idle)
+            && self.next_state != Some(State::Death)
+            && self.next_state != Some(State::FadeIn)
+            && self.next_state != Some(State::FadeOut)
+        {
+            self.next_state = Some(state);
+        }
+    }
+
+    pub(crate) fn update_state(&mut self, state: State, frame: u32, map_data: &Grid) {
+        self.start_frame = frame;
+        self.state = state;
+        self.update_dest(map_data);
+    }
+
+    fn update_dest(&mut self, map_data: &Grid) {
+        let Some(direction) = self.state.direction() else {
+            return;
+        };
+        let old_dest = self.coord_dest;
+        if let Some(dest) = map_data.neighbour(self.coord_dest, direction) {
+            self.coord_dest = dest;
+        }
+
+        let old_tile = map_data[self.coord_src];
+        let new_tile = map_data[self.coord_dest];
+        // The target position is forbidden
+        if !new_tile.can_enter(direction)
+            || !old_tile.can_leave(direction)
+            || new_tile.lock().is_some_and(|color| self.key(color) == 0)
+        {
+            self.coord_dest = old_dest;
+        } else if new_tile == Tile::Hole {
+            self.next_state = Some(State::Death);
+        }
+    }
+
+    pub fn key(&self, color: KeyColor) -> usize {
+        match color {
+            KeyColor::Gray => self.key_gray,
+            KeyColor::Yellow => self.key_yellow,
+            KeyColor::Red => self.key_red,
+        }
+    }
+
+    pub(crate) fn key_mut(&mut self, color: KeyColor) -> &mut usize {
+        match color {
+            KeyColor::Gray => &mut self.key_gray,
+            KeyColor::Yellow => &mut self.key_yellow,
+            KeyColor::Red => &mut self.key_red,
+        }
+    }
+}
+
+impl State {
+    /// The walking direction, for the states that have one.
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            State::Left => Some(Direction::Left),
+            State::Right => Some(Direction::Right),
+            State::Up => Some(Direction::Up),
+            State::Down => Some(Direction::Down),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use bobby_carrot::Direction;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraSettings {
+    /// How far Bobby moves from the center of the view before the camera
+    /// follows, in points.
+    pub dead_zone: u32,
+    /// How far ahead of Bobby the camera looks in the direction he faces, in
+    /// points.
+    pub look_ahead: u32,
+    /// Seconds for the camera to cover most of the way to where it heads, 0
+    /// to follow at once.
+    pub easing: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings {
+            dead_zone: 16,
+            look_ahead: 32,
+            easing: 0.15,
+        }
+    }
+}
+
+/// Follows Bobby around the map, smoothly, instead of keeping him at the
+/// center of the view.
+pub struct Camera {
+    pub settings: CameraSettings,
+    /// Center of the view on the map in points, `None` to jump to Bobby at
+    /// the next update.
+    center: Option<(f32, f32)>,
+}
+
+impl Camera {
+    pub fn new(settings: CameraSettings) -> Camera {
+        Camera {
+            settings,
+            center: None,
+        }
+    }
+
+    /// Jump to Bobby at the next update, such as on a new level.
+    pub fn snap(&mut self) {
+        self.center = None;
+    }
+
+    /// Move towards Bobby at `target`, for `elapsed` since the last update.
+    /// Returns the top left corner of the view on the map, the view staying
+    /// inside the map.
+    pub fn update(
+        &mut self,
+        target: (i32, i32),
+        facing: Option<Direction>,
+        elapsed: Duration,
+        view: (u32, u32),
+        map: (u32, u32),
+    ) -> (i32, i32) {
+        let ahead = self.settings.look_ahead as f32;
+        let (dx, dy) = match facing {
+            Some(Direction::Left) => (-ahead, 0.0),
+            Some(Direction::Right) => (ahead, 0.0),
+            Some(Direction::Up) => (0.0, -ahead),
+            Some(Direction::Down) => (0.0, ahead),
+            None => (0.0, 0.0),
+        };
+        let goal = (target.0 as f32 + dx, target.1 as f32 + dy);
+        let (x, y) = self.center.unwrap_or(goal);
+        let dead_zone = self.settings.dead_zone as f32;
+        let heading = (follow(x, goal.0, dead_zone), follow(y, goal.1, dead_zone));
+        let progress = if self.settings.easing > 0.0 {
+            1.0 - (-elapsed.as_secs_f32() / self.settings.easing).exp()
+        } else {
+            1.0
+        };
+        let x = clamp(x + (heading.0 - x) * progress, view.0, map.0);
+        let y = clamp(y + (heading.1 - y) * progress, view.1, map.1);
+        self.center = Some((x, y));
+        (
+            (x - view.0 as f32 / 2.0).round() as i32,
+            (y - view.1 as f32 / 2.0).round() as i32,
+        )
+    }
+}
+
+/// The closest center to `center` that has `goal` inside the dead zone.
+fn follow(center: f32, goal: f32, dead_zone: f32) -> f32 {
+    if goal > center + dead_zone {
+        goal - dead_zone
+    } else if goal < center - dead_zone {
+        goal + dead_zone
+    } else {
+        center
+    }
+}
+
+/// Keep the view of size `view` centered on `center` inside the map, a map
+/// smaller than the view stays in its top left corner.
+fn clamp(center: f32, view: u32, map: u32) -> f32 {
+    let half = view as f32 / 2.0;
+    center.clamp(half, (map as f32 - half).max(half))
+}
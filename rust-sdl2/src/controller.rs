@@ -0,0 +1,142 @@
+use bobby_carrot::Direction;
+use sdl2::{
+    controller::{Axis, Button, GameController},
+    event::Event,
+    keyboard::{Keycode, Mod},
+    GameControllerSubsystem, Sdl,
+};
+
+use crate::bindings::Action;
+
+/// How far the left stick has to be pushed to move, out of 32767.
+const STICK_DEAD_ZONE: i16 = 12_000;
+
+/// The game controllers plugged in, they can come and go at any time.
+/// Without the controller subsystem the game is played with the keyboard.
+pub struct Controllers {
+    subsystem: Option<GameControllerSubsystem>,
+    open: Vec<GameController>,
+}
+
+impl Controllers {
+    pub fn open(context: &Sdl) -> Controllers {
+        // Better mappings of the common controllers, through the HIDAPI
+        // drivers of SDL
+        sdl2::hint::set("SDL_JOYSTICK_HIDAPI", "1");
+        let subsystem = context
+            .game_controller()
+            .map_err(|err| eprintln!("No game controller: {err}"))
+            .ok();
+        Controllers {
+            subsystem,
+            open: Vec::new(),
+        }
+    }
+
+    /// Call with every event, to open the controllers as they are plugged
+    /// in. SDL reports the ones already there at start up the same way.
+    pub fn handle(&mut self, event: &Event) {
+        let Some(subsystem) = &self.subsystem else {
+            return;
+        };
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => match subsystem.open(which) {
+                Ok(controller) => self.open.push(controller),
+                Err(err) => eprintln!("Can't open game controller {which}: {err}"),
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.open
+                    .retain(|controller| controller.instance_id() != which);
+            }
+            _ => {}
+        }
+    }
+
+    /// The direction held on the D-pad or the left stick of any controller.
+    pub fn direction(&self) -> Option<Direction> {
+        self.open.iter().find_map(|controller| {
+            let pressed = |button| controller.button(button);
+            let x = controller.axis(Axis::LeftX);
+            let y = controller.axis(Axis::LeftY);
+            if pressed(Button::DPadLeft) {
+                Some(Direction::Left)
+            } else if pressed(Button::DPadRight) {
+                Some(Direction::Right)
+            } else if pressed(Button::DPadUp) {
+                Some(Direction::Up)
+            } else if pressed(Button::DPadDown) {
+                Some(Direction::Down)
+            } else if x.unsigned_abs().max(y.unsigned_abs()) < STICK_DEAD_ZONE.unsigned_abs() {
+                None
+            } else if x.unsigned_abs() >= y.unsigned_abs() {
+                // the axis pushed the furthest wins
+                Some(if x < 0 {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                })
+            } else {
+                Some(if y < 0 {
+                    Direction::Up
+                } else {
+                    Direction::Down
+                })
+            }
+        })
+    }
+}
+
+/// The action of a button while playing: A goes to the next level, B
+/// restarts, X goes to the previous level and Y shows the help. Back switches
+/// the full view and Start pauses.
+pub fn game_action(button: Button) -> Option<Action> {
+    let action = match button {
+        Button::A => Action::Next,
+        Button::B => Action::Restart,
+        Button::X => Action::Previous,
+        Button::Y => Action::Help,
+        Button::Back => Action::FullView,
+        Button::Start => Action::Pause,
+        Button::LeftShoulder => Action::Undo,
+        Button::RightShoulder => Action::Hints,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// The key a button stands for in the menu.
+pub fn menu_key(button: Button) -> Option<Keycode> {
+    let code = match button {
+        Button::A | Button::Start => Keycode::Return,
+        Button::B | Button::Back => Keycode::Escape,
+        Button::DPadLeft => Keycode::Left,
+        Button::DPadRight => Keycode::Right,
+        Button::DPadUp => Keycode::Up,
+        Button::DPadDown => Keycode::Down,
+        Button::LeftShoulder => Keycode::PageUp,
+        Button::RightShoulder => Keycode::PageDown,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// A button press turned into the press of the key it stands for, so that
+/// both are handled the same. Other events are left as they are.
+pub fn as_key(event: Event, key: fn(Button) -> Option<Keycode>) -> Event {
+    match event {
+        Event::ControllerButtonDown {
+            timestamp, button, ..
+        } => match key(button) {
+            Some(keycode) => Event::KeyDown {
+                timestamp,
+                window_id: 0,
+                keycode: Some(keycode),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            },
+            None => event,
+        },
+        _ => event,
+    }
+}
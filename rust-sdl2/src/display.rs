@@ -0,0 +1,215 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sdl2::{
+    event::Event,
+    pixels::Color,
+    rect::Rect,
+    render::{BlendMode, WindowCanvas},
+    video::{FullscreenType, Window, WindowPos},
+    VideoSubsystem,
+};
+
+/// Dots per inch of a display the view looks right on at scale 1.
+const BASE_DPI: f32 = 96.0;
+
+/// Shortest time between two frames shown, for when presenting doesn't wait
+/// for the display: without vsync, or while the window is minimized.
+const MIN_FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 240);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WindowOptions {
+    /// Screen units per point of the view, detected from the display if not
+    /// set.
+    pub scale: Option<u32>,
+    pub fullscreen: bool,
+}
+
+/// How the view is shown in a window of any size: scaled up by a whole
+/// number of pixels per point, centered between black bars. A view bigger
+/// than the window is scaled down to fit.
+///
+/// Everything is drawn in points of the view, the window is only seen
+/// through [`Display::begin_frame`], [`Display::set_camera`] and
+/// [`Display::to_view`].
+pub struct Display {
+    /// Size of the window in screen units per point of the view, out of
+    /// fullscreen.
+    scale: u32,
+    fullscreen: bool,
+    /// Size of the view in points, as of the last frame.
+    size: (u32, u32),
+    /// Pixels per point of the view, as of the last frame.
+    zoom: f32,
+    /// Top left corner of the view in the window, in points.
+    origin: (i32, i32),
+    /// Pixels per screen unit, 2 on a Retina display.
+    density: f32,
+    /// When the last frame was shown.
+    presented: Instant,
+}
+
+impl Display {
+    /// A resizable window sized for a view of `width` x `height` points.
+    pub fn open(
+        video: &VideoSubsystem,
+        title: &str,
+        width: u32,
+        height: u32,
+        options: WindowOptions,
+    ) -> Result<(Display, WindowCanvas), Box<dyn std::error::Error>> {
+        let window = video
+            .window(title, width, height)
+            .position_centered()
+            .resizable()
+            .allow_highdpi()
+            .build()?;
+        let mut canvas = window.into_canvas().present_vsync().build()?;
+        canvas.set_blend_mode(BlendMode::Blend);
+        let density = canvas.output_size()?.0 as f32 / width as f32;
+        let scale = options
+            .scale
+            .unwrap_or_else(|| detect_scale(video, canvas.window(), width, height, density));
+        let window = canvas.window_mut();
+        window.set_size(width * scale, height * scale)?;
+        window.set_position(WindowPos::Centered, WindowPos::Centered);
+        if options.fullscreen {
+            window.set_fullscreen(FullscreenType::Desktop)?;
+        }
+        let display = Display {
+            scale,
+            fullscreen: options.fullscreen,
+            size: (width, height),
+            zoom: 1.0,
+            origin: (0, 0),
+            density,
+            presented: Instant::now(),
+        };
+        Ok((display, canvas))
+    }
+
+    /// Fit the window to a view of `width` x `height` points, a fullscreen
+    /// window stays as it is.
+    pub fn resize(
+        &self,
+        canvas: &mut WindowCanvas,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.fullscreen {
+            canvas
+                .window_mut()
+                .set_size(width * self.scale, height * self.scale)?;
+        }
+        Ok(())
+    }
+
+    pub fn toggle_fullscreen(
+        &mut self,
+        canvas: &mut WindowCanvas,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.fullscreen = !self.fullscreen;
+        if self.fullscreen {
+            canvas
+                .window_mut()
+                .set_fullscreen(FullscreenType::Desktop)?;
+        } else {
+            canvas.window_mut().set_fullscreen(FullscreenType::Off)?;
+            // the view may have changed size in the meantime
+            self.resize(canvas, self.size.0, self.size.1)?;
+        }
+        Ok(())
+    }
+
+    /// Clear the window and set up a view of `width` x `height` points, as
+    /// big as fits, with its top left corner at `(0, 0)`.
+    pub fn begin_frame(
+        &mut self,
+        canvas: &mut WindowCanvas,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (output_width, output_height) = canvas.output_size()?;
+        let (window_width, _) = canvas.window().size();
+        self.density = output_width as f32 / window_width.max(1) as f32;
+        self.size = (width, height);
+        // nothing to draw on while minimized
+        if output_width == 0 || output_height == 0 {
+            return Ok(());
+        }
+        let fit = (output_width as f32 / width as f32).min(output_height as f32 / height as f32);
+        self.zoom = if fit >= 1.0 { fit.floor() } else { fit };
+        self.origin = (
+            ((output_width as f32 / self.zoom) as i32 - width as i32) / 2,
+            ((output_height as f32 / self.zoom) as i32 - height as i32) / 2,
+        );
+        canvas.set_scale(self.zoom, self.zoom)?;
+        canvas.set_viewport(None);
+        canvas.set_clip_rect(None);
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        self.set_camera(canvas, 0, 0);
+        Ok(())
+    }
+
+    /// Show the frame drawn, waiting first if the last one was shown less
+    /// than [`MIN_FRAME_TIME`] ago.
+    pub fn present(&mut self, canvas: &mut WindowCanvas) {
+        canvas.present();
+        let elapsed = self.presented.elapsed();
+        if elapsed < MIN_FRAME_TIME {
+            thread::sleep(MIN_FRAME_TIME - elapsed);
+        }
+        self.presented = Instant::now();
+    }
+
+    /// Show the view from `(x, y)` on, what is drawn out of the view is
+    /// clipped.
+    pub fn set_camera(&self, canvas: &mut WindowCanvas, x: i32, y: i32) {
+        let (width, height) = self.size;
+        canvas.set_viewport(Rect::new(
+            self.origin.0 - x,
+            self.origin.1 - y,
+            (width as i32 + x) as u32,
+            (height as i32 + y) as u32,
+        ));
+        // relative to the viewport
+        canvas.set_clip_rect(Rect::new(x, y, width, height));
+    }
+
+    /// A mouse event with its position in points of the view, as drawn at
+    /// the last frame. Other events are left as they are.
+    pub fn to_view(&self, mut event: Event) -> Event {
+        if let Event::MouseMotion { x, y, .. }
+        | Event::MouseButtonDown { x, y, .. }
+        | Event::MouseButtonUp { x, y, .. } = &mut event
+        {
+            let zoom = self.zoom / self.density;
+            *x = (*x as f32 / zoom).floor() as i32 - self.origin.0;
+            *y = (*y as f32 / zoom).floor() as i32 - self.origin.1;
+        }
+        event
+    }
+}
+
+/// The scale that shows the view as big as on a 96 DPI display, as long as
+/// the window fits on the display.
+fn detect_scale(
+    video: &VideoSubsystem,
+    window: &Window,
+    width: u32,
+    height: u32,
+    density: f32,
+) -> u32 {
+    let index = window.display_index().unwrap_or(0);
+    // the DPI is of pixels, a Retina display already doubles them
+    let scale = match video.display_dpi(index) {
+        Ok((_, dpi, _)) => (dpi / density / BASE_DPI).round() as u32,
+        Err(_) => 1,
+    };
+    let fits = match video.display_usable_bounds(index) {
+        Ok(bounds) => (bounds.width() / width).min(bounds.height() / height),
+        Err(_) => scale,
+    };
+    scale.min(fits).max(1)
+}
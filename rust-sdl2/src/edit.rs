@@ -0,0 +1,413 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use bobby_carrot::{
+    text, Editor, GameEvent, GameState, Grid, MapInfo, Tile, FRAMES, HEIGHT_POINTS, WIDTH_POINTS,
+};
+use sdl2::{
+    event::Event,
+    gfx::primitives::DrawRenderer,
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton,
+    pixels::Color,
+    rect::Rect,
+};
+
+use crate::bindings::{Action, Bindings};
+use crate::display::{Display, WindowOptions};
+use crate::{bobby_rects, draw_bobby, draw_tiles, parse_scale, tile_sprite, Assets, MAX_LAG, TICK};
+
+const PALETTE_COLUMNS: u32 = 4;
+const PALETTE_WIDTH: u32 = 32 * PALETTE_COLUMNS;
+/// Characters of the 8px font on a line of the panel.
+const LINE_LEN: usize = PALETTE_WIDTH as usize / 8;
+/// Lines of text below the palette.
+const PANEL_LINES: u32 = 11;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Tool {
+    Paint,
+    /// Flood fill of the area of same tiles.
+    Fill,
+}
+
+/// `edit <file> [--size <w>x<h>] [--scale <n>] [--fullscreen]`: the level
+/// editor, the file is created on the first save if it doesn't exist. Tab
+/// switches between editing and play-testing.
+///
+/// The map takes the left of the window, the palette of every tile of
+/// `tileset.png` the right, with the name of the tiles below it. A new level
+/// is 16x16 tiles unless `--size` is given, Ctrl and the arrows resize it.
+pub fn edit(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "Usage: bobby-carrot edit <file> [--size <w>x<h>] [--scale <n>] [--fullscreen]";
+    let mut path = None;
+    let mut size = None;
+    let mut window_options = WindowOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                let value = args.next().ok_or("Missing size after --size")?;
+                size = Some(parse_size(value).ok_or_else(|| {
+                    format!(
+                        "Invalid --size {value}: expected <w>x<h>, from 1 to {}",
+                        Grid::MAX_SIZE
+                    )
+                })?);
+            }
+            "--scale" => window_options.scale = Some(parse_scale(args.next().cloned())?),
+            "--fullscreen" => window_options.fullscreen = true,
+            _ if path.is_none() => path = Some(Path::new(arg)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+    // a level in the text form is saved back in the text form
+    let (mut editor, as_text) = match fs::read(path) {
+        Ok(data) => (Editor::new(&MapInfo::load(path)?), text::is_text(&data)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let (width, height) = size.unwrap_or((WIDTH_POINTS, HEIGHT_POINTS));
+            (Editor::empty(width, height), false)
+        }
+        Err(err) => return Err(format!("{}: {}", path.display(), err).into()),
+    };
+    if let Some((width, height)) = size {
+        editor.resize(width, height);
+    }
+    let palette = (0..=u8::MAX).map_while(Tile::from_byte).collect::<Vec<_>>();
+
+    let context = sdl2::init()?;
+    let video_subsystem = context.video()?;
+    // the play-test moves with the keys of the game
+    let bindings = match Bindings::default_path() {
+        Some(path) => Bindings::load(&path)?,
+        None => Bindings::default(),
+    };
+    let (width, height) = view_size(editor.tiles(), palette.len());
+    let (mut display, mut canvas) = Display::open(
+        &video_subsystem,
+        format!("Bobby Carrot editor ({})", path.display()).as_str(),
+        width,
+        height,
+        window_options,
+    )?;
+    let texture_creator = canvas.texture_creator();
+    let mut event_pump = context.event_pump()?;
+    let assets = Assets::load_all(&texture_creator)?;
+
+    let mut tool = Tool::Paint;
+    let mut selected = Tile::Floor;
+    let mut painting = false;
+    let mut hovered = None;
+    let mut playing: Option<GameState> = None;
+    let mut status = String::new();
+    // Escape quits at once only without unsaved changes
+    let mut quit_asked = false;
+    let started = Instant::now();
+    let mut last_instant = Instant::now();
+    let mut lag = Duration::ZERO;
+    // Where Bobby was drawn before the last step of the play-test
+    let mut previous_bobby = None;
+
+    'running: loop {
+        let now = Instant::now();
+        lag = (lag + (now - last_instant)).min(MAX_LAG);
+        last_instant = now;
+        for event in event_pump.poll_iter() {
+            match display.to_view(event) {
+                Event::Quit { .. } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(code),
+                    keymod,
+                    ..
+                } => {
+                    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    if code != Keycode::Escape {
+                        quit_asked = false;
+                    }
+                    match code {
+                        Keycode::Tab | Keycode::Escape if playing.is_some() => {
+                            playing = None;
+                            status.clear();
+                        }
+                        Keycode::Tab => {
+                            let map_info = editor.map_info();
+                            status = bobby_carrot::validate(&map_info)
+                                .first()
+                                .map(ToString::to_string)
+                                .unwrap_or_default();
+                            playing = Some(GameState::new(map_info));
+                            painting = false;
+                            lag = Duration::ZERO;
+                            previous_bobby = None;
+                        }
+                        Keycode::Escape => {
+                            if editor.is_modified() && !quit_asked {
+                                status = "Unsaved changes, Esc again to quit".to_string();
+                                quit_asked = true;
+                            } else {
+                                break 'running;
+                            }
+                        }
+                        Keycode::F11 => display.toggle_fullscreen(&mut canvas)?,
+                        _ if playing.is_some() => {
+                            if let (Some(game), Some(Action::Restart)) =
+                                (&mut playing, bindings.action(code))
+                            {
+                                game.restart();
+                                status.clear();
+                            }
+                        }
+                        Keycode::Z if ctrl && shift => {
+                            editor.redo();
+                        }
+                        Keycode::Z if ctrl => {
+                            editor.undo();
+                        }
+                        Keycode::Y if ctrl => {
+                            editor.redo();
+                        }
+                        Keycode::Left | Keycode::Right | Keycode::Up | Keycode::Down if ctrl => {
+                            let (mut width, mut height) =
+                                (editor.tiles().width(), editor.tiles().height());
+                            match code {
+                                Keycode::Left => width -= 1,
+                                Keycode::Right => width += 1,
+                                Keycode::Up => height -= 1,
+                                _ => height += 1,
+                            }
+                            if (1..=Grid::MAX_SIZE).contains(&width)
+                                && (1..=Grid::MAX_SIZE).contains(&height)
+                            {
+                                editor.resize(width, height);
+                            }
+                        }
+                        Keycode::S if ctrl => {
+                            status = match save(path, &editor, as_text) {
+                                Ok(()) => {
+                                    editor.mark_saved();
+                                    format!("Saved {}", path.display())
+                                }
+                                Err(err) => format!("Can't save: {err}"),
+                            };
+                        }
+                        Keycode::F => {
+                            tool = match tool {
+                                Tool::Paint => Tool::Fill,
+                                Tool::Fill => Tool::Paint,
+                            };
+                        }
+                        _ => {}
+                    }
+                }
+                Event::MouseButtonDown {
+                    mouse_btn, x, y, ..
+                } if playing.is_none() => {
+                    if let Some(tile) = palette_at(editor.tiles(), &palette, x, y) {
+                        selected = tile;
+                    } else if let Some((x, y)) = cell_at(editor.tiles(), x, y) {
+                        match mouse_btn {
+                            MouseButton::Left => {
+                                editor.begin_step();
+                                match tool {
+                                    Tool::Paint => {
+                                        editor.paint(x, y, selected);
+                                        painting = true;
+                                    }
+                                    Tool::Fill => {
+                                        editor.fill(x, y, selected);
+                                    }
+                                }
+                            }
+                            MouseButton::Right => selected = editor.tile(x, y),
+                            _ => {}
+                        }
+                    }
+                }
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left,
+                    ..
+                } => painting = false,
+                Event::MouseMotion { x, y, .. } => {
+                    hovered = Some((x, y));
+                    if let (true, Some((x, y))) = (painting, cell_at(editor.tiles(), x, y)) {
+                        editor.paint(x, y, selected);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(game) = &mut playing {
+            while lag >= TICK {
+                lag -= TICK;
+                let direction = bindings.held_direction(&event_pump.keyboard_state());
+                previous_bobby = Some(bobby_rects(game.bobby(), game.frame(), None, 0.0).1);
+                for event in game.step(direction) {
+                    if event == GameEvent::LevelCleared {
+                        status = "Cleared! Tab to edit".to_string();
+                    }
+                }
+            }
+        }
+
+        let (width, height) = view_size(editor.tiles(), palette.len());
+        display.begin_frame(&mut canvas, width, height)?;
+        if let Some(game) = &playing {
+            let alpha = lag.as_secs_f32() / TICK.as_secs_f32();
+            draw_tiles(
+                &mut canvas,
+                &assets,
+                &game.map_info().data,
+                game.frame(),
+                game.is_finished(),
+            )?;
+            draw_bobby(
+                &mut canvas,
+                &assets,
+                game.bobby(),
+                game.frame(),
+                previous_bobby,
+                alpha,
+            )?;
+        } else {
+            let frame = (started.elapsed().as_secs_f32() * FRAMES as f32) as u32;
+            draw_tiles(&mut canvas, &assets, editor.tiles(), frame, false)?;
+            if let Some((x, y)) = hovered.and_then(|(x, y)| cell_at(editor.tiles(), x, y)) {
+                canvas.set_draw_color(Color::RGB(255, 200, 0));
+                canvas.draw_rect(Rect::new(32 * x as i32, 32 * y as i32, 32, 32))?;
+            }
+        }
+
+        // Palette
+        for (idx, tile) in palette.iter().enumerate() {
+            let dest = palette_rect(editor.tiles(), idx);
+            canvas.copy(&assets.tileset_texture, tile_sprite(*tile), dest)?;
+            if *tile == selected {
+                canvas.set_draw_color(Color::RGB(255, 200, 0));
+                canvas.draw_rect(dest)?;
+            }
+        }
+
+        // What is selected and under the mouse, then the keys
+        let mode = match (&playing, tool) {
+            (Some(_), _) => "Playing",
+            (None, Tool::Paint) => "Paint",
+            (None, Tool::Fill) => "Fill",
+        };
+        let modified = if editor.is_modified() { " *" } else { "" };
+        let (map_width, map_height) = (editor.tiles().width(), editor.tiles().height());
+        let hovered_name = match hovered {
+            Some((x, y)) => match (
+                palette_at(editor.tiles(), &palette, x, y),
+                cell_at(editor.tiles(), x, y),
+            ) {
+                (Some(tile), _) => tile.name().to_string(),
+                (_, Some((x, y))) if playing.is_none() => {
+                    format!("{x},{y} {}", editor.tile(x, y).name())
+                }
+                _ => String::new(),
+            },
+            None => String::new(),
+        };
+        let mut lines = vec![
+            (selected.name().to_string(), true),
+            (format!("{mode} {map_width}x{map_height}{modified}"), false),
+            (hovered_name, false),
+        ];
+        let status = status.chars().collect::<Vec<_>>();
+        for line in status.chunks(LINE_LEN).take(2) {
+            lines.push((line.iter().collect(), true));
+        }
+        lines.resize(5, (String::new(), false));
+        for help in [
+            "Tab test F fill",
+            "^Z undo ^Y redo",
+            "^S save Esc quit",
+            "Right click pick",
+            "^Arrows resize",
+            "F11 fullscreen",
+        ] {
+            lines.push((help.to_string(), false));
+        }
+        let y = palette_rect(editor.tiles(), palette.len() - 1).bottom() + 4;
+        for (idx, (line, highlight)) in lines.iter().enumerate() {
+            let color = if *highlight {
+                Color::RGB(255, 200, 0)
+            } else {
+                Color::RGB(255, 255, 255)
+            };
+            canvas.string(
+                32 * map_width as i16,
+                (y + 12 * idx as i32) as i16,
+                &line.chars().take(LINE_LEN).collect::<String>(),
+                color,
+            )?;
+        }
+
+        display.present(&mut canvas);
+    }
+
+    if editor.is_modified() {
+        eprintln!("{}: quit without saving", path.display());
+    }
+    Ok(())
+}
+
+fn save(path: &Path, editor: &Editor, as_text: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = editor.map_info().to_blm();
+    if as_text {
+        data = text::blm_to_text(&data)?.into_bytes();
+    }
+    fs::write(path, data).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(())
+}
+
+/// The size in points of the map with the palette and the panel on its
+/// right.
+fn view_size(map: &Grid, palette_len: usize) -> (u32, u32) {
+    let panel_height = palette_rect(map, palette_len - 1).bottom() as u32 + 4 + 12 * PANEL_LINES;
+    (
+        32 * map.width() + PALETTE_WIDTH,
+        (32 * map.height()).max(panel_height),
+    )
+}
+
+/// `<w>x<h>`, each side from 1 to [`Grid::MAX_SIZE`].
+fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+    let valid = 1..=Grid::MAX_SIZE;
+    (valid.contains(&size.0) && valid.contains(&size.1)).then_some(size)
+}
+
+/// The cell of the map under a point of the view.
+fn cell_at(map: &Grid, x: i32, y: i32) -> Option<(u32, u32)> {
+    if x < 0 || y < 0 {
+        return None;
+    }
+    let cell = (x as u32 / 32, y as u32 / 32);
+    map.contains(cell).then_some(cell)
+}
+
+/// The palette is on the right of the map.
+fn palette_rect(map: &Grid, idx: usize) -> Rect {
+    let idx = idx as u32;
+    Rect::new(
+        (32 * map.width() + 32 * (idx % PALETTE_COLUMNS)) as i32,
+        (32 * (idx / PALETTE_COLUMNS)) as i32,
+        32,
+        32,
+    )
+}
+
+/// The tile of the palette under a point of the view.
+fn palette_at(map: &Grid, palette: &[Tile], x: i32, y: i32) -> Option<Tile> {
+    (0..palette.len())
+        .find(|idx| palette_rect(map, *idx).contains_point((x, y)))
+        .map(|idx| palette[idx])
+}
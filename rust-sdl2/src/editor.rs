@@ -0,0 +1,261 @@
+use crate::game::Direction;
+use crate::grid::Grid;
+use crate::map::MapInfo;
+use crate::tile::Tile;
+
+/// A level being edited, with the history of its edits.
+///
+/// A level has a single start and a single finish tile: painting one of them
+/// turns the previous one into floor.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    tiles: Grid,
+    undo: Vec<Grid>,
+    redo: Vec<Grid>,
+    /// The tiles as they were last saved, empty if never saved.
+    saved: Option<Grid>,
+    /// The next change starts a new undo step.
+    new_step: bool,
+}
+
+impl Editor {
+    pub fn new(map_info: &MapInfo) -> Editor {
+        Editor {
+            tiles: map_info.data.clone(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            saved: Some(map_info.data.clone()),
+            new_step: true,
+        }
+    }
+
+    /// A level of floor only, not saved yet.
+    pub fn empty(width: u32, height: u32) -> Editor {
+        let tiles = Grid::new(width, height, Tile::Floor);
+        let mut editor = Editor::new(&MapInfo::from_tiles(tiles));
+        editor.saved = None;
+        editor
+    }
+
+    pub fn tiles(&self) -> &Grid {
+        &self.tiles
+    }
+
+    pub fn tile(&self, x: u32, y: u32) -> Tile {
+        self.tiles[(x, y)]
+    }
+
+    /// The level as edited so far.
+    pub fn map_info(&self) -> MapInfo {
+        MapInfo::from_tiles(self.tiles.clone())
+    }
+
+    /// Changed since it was last saved.
+    pub fn is_modified(&self) -> bool {
+        self.saved.as_ref() != Some(&self.tiles)
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.tiles.clone());
+    }
+
+    /// The edits from now on until the next call are undone at once, such as
+    /// the tiles painted in one stroke.
+    pub fn begin_step(&mut self) {
+        self.new_step = true;
+    }
+
+    /// Set the tile at `(x, y)`, returns whether it changed.
+    pub fn paint(&mut self, x: u32, y: u32, tile: Tile) -> bool {
+        if self.tiles[(x, y)] == tile {
+            return false;
+        }
+        self.save_step();
+        if matches!(tile, Tile::Start | Tile::Finish) {
+            for other in self
+                .tiles
+                .tiles_mut()
+                .iter_mut()
+                .filter(|other| **other == tile)
+            {
+                *other = Tile::Floor;
+            }
+        }
+        self.tiles[(x, y)] = tile;
+        true
+    }
+
+    /// Replace the area of same tiles around `(x, y)` with `tile`, as one
+    /// undo step. Returns whether anything changed.
+    pub fn fill(&mut self, x: u32, y: u32, tile: Tile) -> bool {
+        let target = self.tile(x, y);
+        // the start and the finish are single tiles
+        if target == tile || matches!(tile, Tile::Start | Tile::Finish) {
+            return self.paint(x, y, tile);
+        }
+        self.begin_step();
+        self.save_step();
+        let mut pending = vec![(x, y)];
+        while let Some(coord) = pending.pop() {
+            if self.tiles[coord] != target {
+                continue;
+            }
+            self.tiles[coord] = tile;
+            for direction in [
+                Direction::Left,
+                Direction::Right,
+                Direction::Up,
+                Direction::Down,
+            ] {
+                pending.extend(self.tiles.neighbour(coord, direction));
+            }
+        }
+        self.begin_step();
+        true
+    }
+
+    /// Crop or extend the level from its top left corner, with floor, as one
+    /// undo step. Returns whether the size changed.
+    pub fn resize(&mut self, width: u32, height: u32) -> bool {
+        if (width, height) == (self.tiles.width(), self.tiles.height()) {
+            return false;
+        }
+        self.begin_step();
+        self.save_step();
+        self.tiles = self.tiles.resized(width, height, Tile::Floor);
+        self.begin_step();
+        true
+    }
+
+    /// Returns whether there was an edit to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(tiles) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push(std::mem::replace(&mut self.tiles, tiles));
+        self.new_step = true;
+        true
+    }
+
+    /// Returns whether there was an undone edit to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(tiles) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push(std::mem::replace(&mut self.tiles, tiles));
+        self.new_step = true;
+        true
+    }
+
+    /// Keep the tiles before the first change of a step.
+    fn save_step(&mut self) {
+        if self.new_step {
+            self.undo.push(self.tiles.clone());
+            self.redo.clear();
+            self.new_step = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(editor: &Editor, tile: Tile) -> usize {
+        editor
+            .tiles()
+            .tiles()
+            .iter()
+            .filter(|t| **t == tile)
+            .count()
+    }
+
+    #[test]
+    fn the_start_and_the_finish_are_moved() {
+        let mut editor = Editor::empty(4, 3);
+        for tile in [Tile::Start, Tile::Finish] {
+            editor.begin_step();
+            editor.paint(0, 0, tile);
+            editor.begin_step();
+            editor.paint(3, 2, tile);
+            assert_eq!(count(&editor, tile), 1, "{tile:?}");
+            assert_eq!(editor.tile(3, 2), tile);
+            assert_eq!(editor.tile(0, 0), Tile::Floor);
+        }
+        // filling with a start paints a single tile
+        editor.fill(1, 1, Tile::Start);
+        assert_eq!(count(&editor, Tile::Start), 1);
+        assert_eq!(editor.tile(1, 1), Tile::Start);
+    }
+
+    #[test]
+    fn a_fill_is_one_undo_step() {
+        let mut editor = Editor::empty(4, 3);
+        editor.begin_step();
+        editor.paint(1, 0, Tile::Wall(0));
+        editor.paint(1, 1, Tile::Wall(0));
+        editor.paint(1, 2, Tile::Wall(0));
+        assert!(editor.fill(0, 0, Tile::Carrot));
+        assert_eq!(count(&editor, Tile::Carrot), 3);
+        assert_eq!(count(&editor, Tile::Floor), 6);
+        assert!(editor.undo());
+        assert_eq!(count(&editor, Tile::Carrot), 0);
+        assert_eq!(count(&editor, Tile::Wall(0)), 3);
+        // the wall painted in one stroke goes at once too
+        assert!(editor.undo());
+        assert_eq!(count(&editor, Tile::Floor), 12);
+        assert!(!editor.undo());
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let mut editor = Editor::empty(3, 3);
+        assert!(editor.is_modified());
+        editor.mark_saved();
+        assert!(!editor.is_modified());
+        let saved = editor.tiles().clone();
+
+        editor.begin_step();
+        editor.paint(0, 0, Tile::Start);
+        editor.begin_step();
+        editor.paint(2, 2, Tile::Egg);
+        let edited = editor.tiles().clone();
+        assert!(editor.is_modified());
+
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert_eq!(editor.tiles(), &saved);
+        assert!(!editor.is_modified());
+        assert!(editor.redo());
+        assert!(editor.redo());
+        assert!(!editor.redo());
+        assert_eq!(editor.tiles(), &edited);
+        editor.mark_saved();
+        assert!(!editor.is_modified());
+
+        // an edit after an undo drops what could be redone
+        assert!(editor.undo());
+        editor.begin_step();
+        editor.paint(1, 1, Tile::Carrot);
+        assert!(!editor.redo());
+        assert!(editor.is_modified());
+    }
+
+    #[test]
+    fn resizing_keeps_the_overlapping_tiles() {
+        let mut editor = Editor::empty(3, 3);
+        editor.begin_step();
+        editor.paint(0, 0, Tile::Start);
+        editor.paint(1, 1, Tile::Carrot);
+        editor.paint(2, 2, Tile::Finish);
+        assert!(editor.resize(2, 4));
+        assert_eq!((editor.tiles().width(), editor.tiles().height()), (2, 4));
+        assert_eq!(editor.tile(0, 0), Tile::Start);
+        assert_eq!(editor.tile(1, 1), Tile::Carrot);
+        assert_eq!(count(&editor, Tile::Finish), 0);
+        assert_eq!(editor.tile(1, 3), Tile::Floor);
+        assert!(!editor.resize(2, 4));
+        assert!(editor.undo());
+        assert_eq!(editor.tile(2, 2), Tile::Finish);
+    }
+}
@@ -0,0 +1,414 @@
+use std::sync::Arc;
+
+use crate::bobby::{Bobby, State};
+use crate::map::{Goal, MapInfo};
+use crate::tile::Tile;
+use crate::undo::Snapshot;
+use crate::FRAMES;
+
+/// Bobby goes idle after standing still for this many frames.
+const IDLE_FRAMES: u32 = 4 * FRAMES as u32;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+}
+
+impl From<Direction> for State {
+    fn from(direction: Direction) -> State {
+        match direction {
+            Direction::Left => State::Left,
+            Direction::Right => State::Right,
+            Direction::Up => State::Up,
+            Direction::Down => State::Down,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KeyColor {
+    Gray,
+    Yellow,
+    Red,
+}
+
+/// Things that happened during one [`GameState::step`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GameEvent {
+    /// Bobby finished a step between two tiles.
+    Moved {
+        from: (u32, u32),
+        to: (u32, u32),
+    },
+    CarrotCollected,
+    EggCollected,
+    KeyCollected(KeyColor),
+    LockOpened(KeyColor),
+    RedSwitchToggled,
+    YellowSwitchToggled,
+    /// The tile at the given coordinate crumbled into a hole.
+    TileCrumbled((u32, u32)),
+    Died,
+    /// The death animation is over and the level was reset.
+    Respawned,
+    /// Everything is collected and Bobby stands on the finish tile.
+    Finished,
+    /// The fade out after [`GameEvent::Finished`] is over.
+    LevelCleared,
+}
+
+/// The complete state of one level being played.
+#[derive(Clone)]
+pub struct GameState {
+    map_info_fresh: Arc<MapInfo>,
+    map_info: MapInfo,
+    bobby: Bobby,
+    frame: u32,
+}
+
+impl GameState {
+    pub fn new(map_info: MapInfo) -> GameState {
+        let bobby = Bobby::new(0, map_info.coord_start);
+        GameState {
+            map_info_fresh: Arc::new(map_info.clone()),
+            map_info,
+            bobby,
+            frame: 0,
+        }
+    }
+
+    /// Reset the level to how it was loaded.
+    pub fn restart(&mut self) {
+        self.map_info = MapInfo::clone(&self.map_info_fresh);
+        self.bobby = Bobby::new(self.frame, self.map_info.coord_start);
+    }
+
+    /// Save the level and Bobby, see [`crate::UndoStack`].
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            data: self.map_info.data.clone(),
+            bobby: self.bobby.clone(),
+        }
+    }
+
+    /// Put back the level and Bobby as they were saved, Bobby standing still
+    /// on his tile.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.map_info.data.clone_from(&snapshot.data);
+        let bobby = &snapshot.bobby;
+        self.bobby = Bobby {
+            state: bobby.state,
+            start_frame: self.frame,
+            last_action_frame: self.frame,
+            carrot_count: bobby.carrot_count,
+            egg_count: bobby.egg_count,
+            key_gray: bobby.key_gray,
+            key_yellow: bobby.key_yellow,
+            key_red: bobby.key_red,
+            ..Bobby::new(self.frame, bobby.coord_src)
+        };
+    }
+
+    /// Advance the simulation by one frame, with the direction currently held
+    /// by the player (if any).
+    pub fn step(&mut self, input: Option<Direction>) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        if self.bobby.faded_out {
+            return events;
+        }
+        self.frame += 1;
+        let frame = self.frame;
+
+        if let Some(direction) = input {
+            self.bobby.last_action_frame = frame;
+            if !self.bobby.is_walking() {
+                self.bobby
+                    .update_state(direction.into(), frame, &self.map_info.data);
+            } else {
+                self.bobby.update_next_state(direction.into(), frame);
+            }
+        }
+
+        if self.bobby.dead {
+            self.restart();
+            events.push(GameEvent::Respawned);
+        } else if self.is_finished() && self.current_tile() == Tile::Finish {
+            if self.bobby.state != State::FadeOut {
+                self.bobby.start_frame = frame;
+                self.bobby.state = State::FadeOut;
+                events.push(GameEvent::Finished);
+            }
+        } else {
+            let bobby = &mut self.bobby;
+            if frame - bobby.last_action_frame >= IDLE_FRAMES
+                && !bobby.is_walking()
+                && bobby.state != State::Idle
+                && bobby.state != State::Death
+                && bobby.state != State::FadeIn
+                && bobby.state != State::FadeOut
+                && bobby.next_state.is_none()
+            {
+                bobby.start_frame = frame;
+                bobby.state = State::Idle;
+            }
+        }
+
+        self.bobby
+            .update(frame, &mut self.map_info.data, &mut events);
+        events
+    }
+
+    pub fn map_info(&self) -> &MapInfo {
+        &self.map_info
+    }
+
+    pub fn bobby(&self) -> &Bobby {
+        &self.bobby
+    }
+
+    /// The number of frames simulated so far.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// All carrots (or eggs, on egg levels) are collected.
+    pub fn is_finished(&self) -> bool {
+        match self.map_info.goal {
+            Goal::Carrots => self.bobby.carrot_count == self.map_info.carrot_total,
+            Goal::Eggs => self.bobby.egg_count == self.map_info.egg_total,
+        }
+    }
+
+    /// The fade out after reaching the finish is over.
+    pub fn is_cleared(&self) -> bool {
+        self.bobby.faded_out
+    }
+
+    /// Bobby stands still, the next direction pressed starts a move.
+    pub fn is_at_rest(&self) -> bool {
+        let bobby = &self.bobby;
+        !bobby.is_walking()
+            && bobby.next_state.is_none()
+            && bobby.state != State::Death
+            && bobby.state != State::FadeIn
+            && bobby.state != State::FadeOut
+    }
+
+    /// The tile Bobby stands on, or walks away from.
+    pub fn current_tile(&self) -> Tile {
+        self.map_info.data[self.bobby.coord_src]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    /// A level of one or more rows of [`Tile::token`]s, Bobby standing on
+    /// the start once faded in.
+    fn level(rows: &[&str]) -> GameState {
+        let width = rows[0].split_whitespace().count() as u32;
+        let tiles = rows
+            .iter()
+            .flat_map(|row| row.split_whitespace())
+            .map(|token| Tile::from_token(token).expect(token))
+            .collect();
+        let grid = Grid::from_tiles(width, rows.len() as u32, tiles);
+        let mut game = GameState::new(MapInfo::from_tiles(grid));
+        settle(&mut game);
+        game
+    }
+
+    /// Run without input until Bobby stands still or the level is cleared.
+    fn settle(game: &mut GameState) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        for _ in 0..1000 {
+            if game.is_at_rest() || game.is_cleared() {
+                return events;
+            }
+            events.extend(game.step(None));
+        }
+        panic!("Bobby never stands still");
+    }
+
+    /// Press `direction` once, then wait for everything it causes.
+    fn walk(game: &mut GameState, direction: Direction) -> Vec<GameEvent> {
+        let mut events = game.step(Some(direction));
+        events.extend(settle(game));
+        events
+    }
+
+    fn tile(game: &GameState, coord: (u32, u32)) -> Tile {
+        game.map_info().data[coord]
+    }
+
+    #[test]
+    fn walls_and_the_edge_block() {
+        let mut game = level(&["St #0", ".. .."]);
+        assert!(walk(&mut game, Direction::Right).is_empty());
+        assert!(walk(&mut game, Direction::Left).is_empty());
+        assert!(walk(&mut game, Direction::Up).is_empty());
+        assert_eq!(game.bobby().coord_src, (0, 0));
+        let events = walk(&mut game, Direction::Down);
+        assert_eq!(
+            events,
+            [GameEvent::Moved {
+                from: (0, 0),
+                to: (0, 1)
+            }]
+        );
+    }
+
+    #[test]
+    fn carrots_are_eaten_then_the_finish_clears() {
+        let mut game = level(&["St ca Fi"]);
+        assert!(walk(&mut game, Direction::Right).contains(&GameEvent::CarrotCollected));
+        assert_eq!(tile(&game, (1, 0)), Tile::CarrotHole);
+        assert!(game.is_finished());
+        walk(&mut game, Direction::Right);
+        // reaching the finish is noticed on the next frame
+        let mut events = game.step(None);
+        events.extend(settle(&mut game));
+        assert!(events.contains(&GameEvent::Finished));
+        assert!(events.contains(&GameEvent::LevelCleared));
+        assert!(game.is_cleared());
+    }
+
+    #[test]
+    fn locks_need_a_key_of_their_color() {
+        let mut game = level(&["ky St Lg .."]);
+        assert!(walk(&mut game, Direction::Right).is_empty());
+        let events = walk(&mut game, Direction::Left);
+        assert!(events.contains(&GameEvent::KeyCollected(KeyColor::Yellow)));
+        assert_eq!(tile(&game, (0, 0)), Tile::Floor);
+        walk(&mut game, Direction::Right);
+        assert!(walk(&mut game, Direction::Right).is_empty());
+
+        let mut game = level(&["kg St Lg .."]);
+        walk(&mut game, Direction::Left);
+        walk(&mut game, Direction::Right);
+        assert_eq!(game.bobby().key_gray, 1);
+        let events = walk(&mut game, Direction::Right);
+        assert!(events.contains(&GameEvent::LockOpened(KeyColor::Gray)));
+        assert_eq!(tile(&game, (2, 0)), Tile::Floor);
+        assert_eq!(game.bobby().key_gray, 0);
+    }
+
+    #[test]
+    fn eggs_are_placed_when_stepping_off() {
+        let mut game = level(&["St eg .."]);
+        assert!(!walk(&mut game, Direction::Right).contains(&GameEvent::EggCollected));
+        assert_eq!(tile(&game, (1, 0)), Tile::Egg);
+        assert!(walk(&mut game, Direction::Right).contains(&GameEvent::EggCollected));
+        assert_eq!(tile(&game, (1, 0)), Tile::EggPlaced);
+        assert_eq!(game.bobby().egg_count, 1);
+        assert!(game.is_finished());
+        // a placed egg can't be walked over again
+        assert!(walk(&mut game, Direction::Left).is_empty());
+    }
+
+    #[test]
+    fn crumbling_floor_turns_into_a_deadly_hole() {
+        let mut game = level(&["St cr .."]);
+        walk(&mut game, Direction::Right);
+        assert!(walk(&mut game, Direction::Right).contains(&GameEvent::TileCrumbled((1, 0))));
+        assert_eq!(tile(&game, (1, 0)), Tile::Hole);
+        let events = walk(&mut game, Direction::Left);
+        assert!(events.contains(&GameEvent::Died));
+        assert!(events.contains(&GameEvent::Respawned));
+        // the level is reset along with Bobby
+        assert_eq!(game.bobby().coord_src, (0, 0));
+        assert_eq!(tile(&game, (1, 0)), Tile::Crumble);
+    }
+
+    #[test]
+    fn conveyors_push_and_block_against_the_flow() {
+        let mut game = level(&["St >> .. .."]);
+        walk(&mut game, Direction::Right);
+        assert_eq!(game.bobby().coord_src, (2, 0));
+        // a conveyor can only be left along its flow
+        let mut game = level(&["St << .."]);
+        assert!(walk(&mut game, Direction::Right).is_empty());
+        let mut game = level(&["St", ">>", ".."]);
+        assert!(walk(&mut game, Direction::Down).is_empty());
+    }
+
+    #[test]
+    fn rails_turn_when_stepping_off() {
+        let mut game = level(&["St -- ..", ".. .. .."]);
+        walk(&mut game, Direction::Right);
+        assert!(walk(&mut game, Direction::Down).is_empty());
+        walk(&mut game, Direction::Right);
+        assert_eq!(tile(&game, (1, 0)), Tile::RailVertical);
+        assert!(walk(&mut game, Direction::Left).is_empty());
+
+        let mut game = level(&["St RD"]);
+        assert!(walk(&mut game, Direction::Right).is_empty());
+    }
+
+    #[test]
+    fn switches_toggle_their_group() {
+        let mut game = level(&["St r0 -- RD y0 >> ^^ r1 y1"]);
+        assert!(walk(&mut game, Direction::Right).contains(&GameEvent::RedSwitchToggled));
+        let tiles = game.map_info().data.tiles().to_vec();
+        assert_eq!(
+            tiles,
+            [
+                Tile::Start,
+                Tile::RedSwitchToggled,
+                Tile::RailVertical,
+                Tile::RailLeftDown,
+                Tile::YellowSwitch,
+                Tile::ConveyorRight,
+                Tile::ConveyorUp,
+                Tile::RedSwitch,
+                Tile::YellowSwitchToggled,
+            ]
+        );
+
+        let mut game = level(&["St y0 >> ^^ r0 y1"]);
+        assert!(walk(&mut game, Direction::Right).contains(&GameEvent::YellowSwitchToggled));
+        let tiles = game.map_info().data.tiles().to_vec();
+        assert_eq!(
+            tiles,
+            [
+                Tile::Start,
+                Tile::YellowSwitchToggled,
+                Tile::ConveyorLeft,
+                Tile::ConveyorDown,
+                Tile::RedSwitch,
+                Tile::YellowSwitch,
+            ]
+        );
+    }
+
+    #[test]
+    fn toggled_switches_do_nothing() {
+        let mut game = level(&["St r1 -- r0 y1 >>"]);
+        let before = game.map_info().data.clone();
+        let events = walk(&mut game, Direction::Right);
+        assert!(!events.contains(&GameEvent::RedSwitchToggled));
+        assert_eq!(game.map_info().data, before);
+
+        let mut game = level(&["St", "y1", ">>"]);
+        let before = game.map_info().data.clone();
+        let events = walk(&mut game, Direction::Down);
+        assert!(!events.contains(&GameEvent::YellowSwitchToggled));
+        assert_eq!(game.map_info().data, before);
+    }
+}
@@ -0,0 +1,554 @@
+//! Random levels that are known to be solvable.
+//!
+//! A level is laid out from a seed in a few passes: the walkable area, the
+//! start and the finish, what is to be collected, then the mechanics on top.
+//! Carrot levels are a maze with a few loops, egg levels a long path folded
+//! on itself, with every tile of it an egg.
+//!
+//! Every candidate is then played by the solver, the ones it can't finish are
+//! thrown away and the next one from the same seed is tried. The same seed
+//! and options always give the same level.
+
+use std::collections::VecDeque;
+
+use crate::game::{Direction, GameState, KeyColor};
+use crate::grid::Grid;
+use crate::map::{Goal, MapInfo};
+use crate::solver::{solve_with_limit, Solution};
+use crate::tile::Tile;
+use crate::{HEIGHT_POINTS, WIDTH_POINTS};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Right,
+    Direction::Up,
+    Direction::Down,
+];
+
+/// The hardest difficulty, the easiest one is 1.
+pub const MAX_DIFFICULTY: u32 = 5;
+
+/// Candidates tried for a seed before settling for the hardest solvable one.
+const MAX_ATTEMPTS: usize = 64;
+
+/// States the solver explores on a candidate layout before counting it as
+/// unsolvable. A seed solves up to [`MAX_ATTEMPTS`] candidates, so this bounds
+/// the time a seed takes: a layout needing more is thrown away even if it has
+/// a solution.
+const MAX_STATES: usize = 200_000;
+
+/// The grass around the walkable area.
+const WALL: Tile = Tile::Wall(0);
+
+/// Which mechanics a level may use, besides the carrots or the eggs.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Mechanics {
+    /// Keys and the locks they open.
+    pub keys: bool,
+    /// Rails, turned by a red switch and every time Bobby walks off them.
+    pub rails: bool,
+    /// Conveyors, reversed by a yellow switch.
+    pub conveyors: bool,
+    /// Floor that breaks once walked over.
+    pub crumble: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct GenerateOptions {
+    pub goal: Goal,
+    pub mechanics: Mechanics,
+    /// From 1 to [`MAX_DIFFICULTY`]: the size of the level, how much is to be
+    /// collected, how many mechanics and how many moves to finish it.
+    pub difficulty: u32,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> GenerateOptions {
+        GenerateOptions {
+            goal: Goal::Carrots,
+            mechanics: Mechanics::default(),
+            difficulty: 3,
+        }
+    }
+}
+
+/// A generated level, with the moves the solver finished it in.
+pub struct Generated {
+    pub map_info: MapInfo,
+    pub moves: Vec<Direction>,
+}
+
+/// A 16x16 level from `seed`, finished by the solver in at least 8 moves per
+/// level of difficulty, or as close to that as the candidates got.
+///
+/// `None` if no candidate could be solved, which takes a seed and options
+/// that leave hardly any way through, such as every mechanic at once on an
+/// egg level.
+///
+/// # Panics
+///
+/// If the difficulty isn't from 1 to [`MAX_DIFFICULTY`].
+pub fn generate(seed: u64, options: &GenerateOptions) -> Option<Generated> {
+    assert!(
+        (1..=MAX_DIFFICULTY).contains(&options.difficulty),
+        "difficulty {}",
+        options.difficulty
+    );
+    let target = 8 * options.difficulty as usize;
+    let mut rng = Rng(seed);
+    let mut best: Option<Generated> = None;
+    for _ in 0..MAX_ATTEMPTS {
+        let map_info = MapInfo::from_tiles(layout(&mut rng, options));
+        let Some(Solution::Solved(moves)) =
+            solve_with_limit(&GameState::new(map_info.clone()), MAX_STATES)
+        else {
+            continue;
+        };
+        let done = moves.len() >= target;
+        if best
+            .as_ref()
+            .is_none_or(|best| moves.len() > best.moves.len())
+        {
+            best = Some(Generated { map_info, moves });
+        }
+        if done {
+            break;
+        }
+    }
+    best
+}
+
+/// One candidate level, solvable or not.
+fn layout(rng: &mut Rng, options: &GenerateOptions) -> Grid {
+    let difficulty = options.difficulty;
+    // an area of 5x5 tiles at difficulty 1, up to 13x13
+    let cells = 2 + difficulty;
+    let size = 2 * cells - 1;
+    let offset = ((WIDTH_POINTS - size) / 2, (HEIGHT_POINTS - size) / 2);
+    let mut grid = Grid::new(WIDTH_POINTS, HEIGHT_POINTS, WALL);
+
+    // The tiles Bobby walks through in a row, where the mechanics that fit
+    // a corridor go, with the sides he comes in and goes out through. Egg
+    // levels are finished along their path, the mechanics are laid out for
+    // it
+    let (passages, path) = match options.goal {
+        Goal::Carrots => {
+            carve_maze(rng, &mut grid, offset, cells, 30 - 5 * difficulty);
+            let floor = floor_tiles(&grid);
+            let start = floor[rng.below(floor.len())];
+            let finish = farthest(&grid, start);
+            grid[start] = Tile::Start;
+            grid[finish] = Tile::Finish;
+            let mut free = floor_tiles(&grid);
+            rng.shuffle(&mut free);
+            for coord in free.into_iter().take(2 + 2 * difficulty as usize) {
+                grid[coord] = Tile::Carrot;
+            }
+            (corridors(&grid), Vec::new())
+        }
+        Goal::Eggs => {
+            let path = walk(rng, &mut grid, offset, size, 8 + 8 * difficulty as usize);
+            for coord in &path {
+                grid[*coord] = Tile::Egg;
+            }
+            grid[path[0]] = Tile::Start;
+            grid[path[path.len() - 1]] = Tile::Finish;
+            let passages = (1..path.len() - 1)
+                .map(|idx| {
+                    let side = |other: (u32, u32)| {
+                        DIRECTIONS
+                            .into_iter()
+                            .find(|direction| grid.neighbour(path[idx], *direction) == Some(other))
+                            .expect("the path goes from tile to tile")
+                    };
+                    (path[idx], side(path[idx - 1]), side(path[idx + 1]))
+                })
+                .collect();
+            (passages, path)
+        }
+    };
+    // where a tile is on the path of an egg level
+    let order = |coord: (u32, u32)| path.iter().position(|other| *other == coord);
+    let mut passages = passages;
+    rng.shuffle(&mut passages);
+    // one of each mechanic at difficulty 1, up to 3
+    let count = 1 + difficulty as usize / 2;
+    let mechanics = options.mechanics;
+
+    if mechanics.keys {
+        let colors = [KeyColor::Gray, KeyColor::Yellow, KeyColor::Red];
+        for color in colors.into_iter().take(count) {
+            let Some((lock, _, _)) = take(&grid, &mut passages) else {
+                break;
+            };
+            let (key, lock_tile) = match color {
+                KeyColor::Gray => (Tile::KeyGray, Tile::LockGray),
+                KeyColor::Yellow => (Tile::KeyYellow, Tile::LockYellow),
+                KeyColor::Red => (Tile::KeyRed, Tile::LockRed),
+            };
+            grid[lock] = lock_tile;
+            // on the way to the lock
+            place(rng, &mut grid, key, |coord| {
+                path.is_empty() || order(coord) < order(lock)
+            });
+        }
+    }
+    if mechanics.crumble {
+        for _ in 0..count {
+            if let Some((coord, _, _)) = take(&grid, &mut passages) {
+                grid[coord] = Tile::Crumble;
+            }
+        }
+    }
+    if mechanics.rails {
+        let switch = place(rng, &mut grid, Tile::RedSwitch, |_| true).and_then(order);
+        for _ in 0..count {
+            let Some((coord, from, to)) = take(&grid, &mut passages) else {
+                break;
+            };
+            let rail = rail(from, to);
+            grid[coord] = match (switch, order(coord)) {
+                // the switch on the way turns it right
+                (Some(switch), Some(idx)) if switch < idx => untoggled(rail),
+                (Some(_), Some(_)) => rail,
+                // turned out of the way, for the red switch to put it back
+                _ => (0..rng.below(4)).fold(rail, |rail, _| rail.toggled()),
+            };
+        }
+    }
+    if mechanics.conveyors {
+        let switch = place(rng, &mut grid, Tile::YellowSwitch, |_| true).and_then(order);
+        let mut placed = 0;
+        while placed < count {
+            let Some((coord, from, to)) = take(&grid, &mut passages) else {
+                break;
+            };
+            // only in a straight line
+            if from != to.opposite() {
+                continue;
+            }
+            grid[coord] = match (switch, order(coord)) {
+                (Some(switch), Some(idx)) if switch < idx => untoggled(conveyor(to)),
+                (Some(_), Some(_)) => conveyor(to),
+                _ => conveyor(if rng.below(2) == 0 { from } else { to }),
+            };
+            placed += 1;
+        }
+    }
+    grid
+}
+
+/// A maze of `cells` x `cells` rooms one tile apart from `offset` on, then
+/// `loops` percent of the walls left between two rooms opened.
+fn carve_maze(rng: &mut Rng, grid: &mut Grid, offset: (u32, u32), cells: u32, loops: u32) {
+    let room = |(x, y): (u32, u32)| (offset.0 + 2 * x, offset.1 + 2 * y);
+    let mut visited = Grid::new(cells, cells, WALL);
+    let first = (
+        rng.below(cells as usize) as u32,
+        rng.below(cells as usize) as u32,
+    );
+    visited[first] = Tile::Floor;
+    grid[room(first)] = Tile::Floor;
+    let mut stack = vec![first];
+    while let Some(&cell) = stack.last() {
+        let mut next = DIRECTIONS
+            .into_iter()
+            .filter_map(|direction| visited.neighbour(cell, direction))
+            .filter(|next| visited[*next] == WALL)
+            .collect::<Vec<_>>();
+        if next.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let next = next.swap_remove(rng.below(next.len()));
+        let (x0, y0) = room(cell);
+        let (x1, y1) = room(next);
+        visited[next] = Tile::Floor;
+        grid[((x0 + x1) / 2, (y0 + y1) / 2)] = Tile::Floor;
+        grid[(x1, y1)] = Tile::Floor;
+        stack.push(next);
+    }
+    let size = 2 * cells - 1;
+    for y in 0..size {
+        for x in 0..size {
+            // between two rooms of a row or of a column
+            if (x + y) % 2 == 1 && rng.below(100) < loops as usize {
+                grid[(offset.0 + x, offset.1 + y)] = Tile::Floor;
+            }
+        }
+    }
+}
+
+/// A path of up to `len` tiles that never crosses itself, inside the square
+/// of `size` tiles from `offset` on. The next tile is the one with the fewest
+/// ways on, most of the time, so that the path fills the square rather than
+/// getting stuck early.
+fn walk(
+    rng: &mut Rng,
+    grid: &mut Grid,
+    offset: (u32, u32),
+    size: u32,
+    len: usize,
+) -> Vec<(u32, u32)> {
+    let inside = |(x, y): (u32, u32)| {
+        (offset.0..offset.0 + size).contains(&x) && (offset.1..offset.1 + size).contains(&y)
+    };
+    let start = (
+        offset.0 + rng.below(size as usize) as u32,
+        offset.1 + rng.below(size as usize) as u32,
+    );
+    let mut path = vec![start];
+    grid[start] = Tile::Floor;
+    while path.len() < len {
+        let free = |grid: &Grid, coord: (u32, u32)| {
+            DIRECTIONS
+                .into_iter()
+                .filter_map(|direction| grid.neighbour(coord, direction))
+                .filter(|next| inside(*next) && grid[*next] == WALL)
+                .collect::<Vec<_>>()
+        };
+        let mut next = free(grid, path[path.len() - 1]);
+        if next.is_empty() {
+            break;
+        }
+        rng.shuffle(&mut next);
+        if rng.below(4) != 0 {
+            next.sort_by_key(|coord| free(grid, *coord).len());
+        }
+        grid[next[0]] = Tile::Floor;
+        path.push(next[0]);
+    }
+    path
+}
+
+/// Every floor tile, row by row.
+fn floor_tiles(grid: &Grid) -> Vec<(u32, u32)> {
+    (0..grid.tiles().len())
+        .filter(|idx| grid.tiles()[*idx] == Tile::Floor)
+        .map(|idx| grid.coord(idx))
+        .collect()
+}
+
+/// The sides of a tile Bobby can walk to, the ones without a wall.
+fn open_sides(grid: &Grid, coord: (u32, u32)) -> Vec<Direction> {
+    DIRECTIONS
+        .into_iter()
+        .filter(|direction| {
+            grid.neighbour(coord, *direction)
+                .is_some_and(|next| grid[next] != WALL)
+        })
+        .collect()
+}
+
+/// The floor tiles with exactly two ways out, and those two ways.
+fn corridors(grid: &Grid) -> Vec<Passage> {
+    floor_tiles(grid)
+        .into_iter()
+        .filter_map(|coord| match *open_sides(grid, coord) {
+            [from, to] => Some((coord, from, to)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The tile the furthest walk away from `start`.
+fn farthest(grid: &Grid, start: (u32, u32)) -> (u32, u32) {
+    let mut seen = vec![false; grid.tiles().len()];
+    seen[grid.index(start)] = true;
+    let mut queue = VecDeque::from([start]);
+    let mut last = start;
+    while let Some(coord) = queue.pop_front() {
+        last = coord;
+        for direction in open_sides(grid, coord) {
+            let next = grid.neighbour(coord, direction).expect("an open side");
+            if !seen[grid.index(next)] {
+                seen[grid.index(next)] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+    last
+}
+
+type Passage = ((u32, u32), Direction, Direction);
+
+/// The next passage still free of any mechanic, keys may have been put on
+/// some since they were found.
+fn take(grid: &Grid, passages: &mut Vec<Passage>) -> Option<Passage> {
+    while let Some(passage) = passages.pop() {
+        if matches!(grid[passage.0], Tile::Floor | Tile::Egg) {
+            return Some(passage);
+        }
+    }
+    None
+}
+
+/// Put `tile` on a random floor or egg tile that is `allowed`, if there is
+/// one left, and tell where.
+fn place(
+    rng: &mut Rng,
+    grid: &mut Grid,
+    tile: Tile,
+    allowed: impl Fn((u32, u32)) -> bool,
+) -> Option<(u32, u32)> {
+    let free = (0..grid.tiles().len())
+        .map(|idx| grid.coord(idx))
+        .filter(|coord| matches!(grid[*coord], Tile::Floor | Tile::Egg) && allowed(*coord))
+        .collect::<Vec<_>>();
+    if free.is_empty() {
+        return None;
+    }
+    let coord = free[rng.below(free.len())];
+    grid[coord] = tile;
+    Some(coord)
+}
+
+/// The tile that its switch turns into `tile`.
+fn untoggled(tile: Tile) -> Tile {
+    let mut untoggled = tile;
+    while untoggled.toggled() != tile {
+        untoggled = untoggled.toggled();
+    }
+    untoggled
+}
+
+/// The rail open on the two sides.
+fn rail(from: Direction, to: Direction) -> Tile {
+    let has = |side| from == side || to == side;
+    if has(Direction::Left) && has(Direction::Right) {
+        Tile::RailHorizontal
+    } else if has(Direction::Up) && has(Direction::Down) {
+        Tile::RailVertical
+    } else if has(Direction::Right) && has(Direction::Down) {
+        Tile::RailRightDown
+    } else if has(Direction::Left) && has(Direction::Down) {
+        Tile::RailLeftDown
+    } else if has(Direction::Left) {
+        Tile::RailLeftUp
+    } else {
+        Tile::RailRightUp
+    }
+}
+
+fn conveyor(direction: Direction) -> Tile {
+    match direction {
+        Direction::Left => Tile::ConveyorLeft,
+        Direction::Right => Tile::ConveyorRight,
+        Direction::Up => Tile::ConveyorUp,
+        Direction::Down => Tile::ConveyorDown,
+    }
+}
+
+/// SplitMix64: the same numbers from a seed everywhere, with no dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 to `n` excluded.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1..items.len()).rev() {
+            items.swap(idx, self.below(idx + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::SwitchColor;
+    use crate::validate::validate;
+
+    /// Each mechanic on its own, then none.
+    fn mechanics() -> [Mechanics; 5] {
+        let none = Mechanics::default();
+        [
+            Mechanics { keys: true, ..none },
+            Mechanics {
+                rails: true,
+                ..none
+            },
+            Mechanics {
+                conveyors: true,
+                ..none
+            },
+            Mechanics {
+                crumble: true,
+                ..none
+            },
+            none,
+        ]
+    }
+
+    #[test]
+    fn a_seed_always_gives_the_same_level() {
+        let options = GenerateOptions {
+            difficulty: 1,
+            ..GenerateOptions::default()
+        };
+        let first = generate(7, &options).expect("a level");
+        let second = generate(7, &options).expect("a level");
+        assert_eq!(first.map_info.to_blm(), second.map_info.to_blm());
+        assert_eq!(first.moves, second.moves);
+    }
+
+    /// A level of the lowest difficulty for each goal and each of
+    /// [`mechanics`].
+    fn every_kind() -> Vec<(GenerateOptions, Generated)> {
+        [Goal::Carrots, Goal::Eggs]
+            .into_iter()
+            .flat_map(|goal| {
+                mechanics().map(|mechanics| GenerateOptions {
+                    goal,
+                    mechanics,
+                    difficulty: 1,
+                })
+            })
+            .map(|options| {
+                let generated = generate(1, &options).expect("a level");
+                assert_eq!(generated.map_info.goal, options.goal);
+                // the mechanics asked for, and only those
+                let tiles = generated.map_info.data.tiles();
+                let has = |wanted: fn(&Tile) -> bool| tiles.iter().any(wanted);
+                let rail = |tile: &Tile| {
+                    tile.switch_group() == Some(SwitchColor::Red) && tile.switch().is_none()
+                };
+                let mechanics = options.mechanics;
+                assert_eq!(has(|tile| tile.lock().is_some()), mechanics.keys);
+                assert_eq!(has(rail), mechanics.rails);
+                assert_eq!(has(|tile| tile.conveyor().is_some()), mechanics.conveyors);
+                assert_eq!(has(|tile| *tile == Tile::Crumble), mechanics.crumble);
+                (options, generated)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn levels_are_solved() {
+        for (options, generated) in every_kind() {
+            let game = GameState::new(generated.map_info);
+            assert_eq!(
+                solve_with_limit(&game, MAX_STATES),
+                Some(Solution::Solved(generated.moves)),
+                "{options:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn levels_are_valid() {
+        for (options, generated) in every_kind() {
+            assert_eq!(validate(&generated.map_info), [], "{options:?}");
+        }
+    }
+}
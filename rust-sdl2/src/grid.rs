@@ -0,0 +1,111 @@
+use std::ops::{Index, IndexMut};
+
+use crate::game::Direction;
+use crate::tile::Tile;
+
+/// The tiles of a level row by row, with its size in tiles.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Grid {
+    width: u32,
+    height: u32,
+    tiles: Vec<Tile>,
+}
+
+impl Grid {
+    /// The size of a side is stored in one byte in the `.blm` files.
+    pub const MAX_SIZE: u32 = u8::MAX as u32;
+
+    /// A grid of `width` x `height` times the same tile.
+    pub fn new(width: u32, height: u32, tile: Tile) -> Grid {
+        Grid::from_tiles(width, height, vec![tile; (width * height) as usize])
+    }
+
+    /// # Panics
+    ///
+    /// If there isn't exactly `width` x `height` tiles.
+    pub fn from_tiles(width: u32, height: u32, tiles: Vec<Tile>) -> Grid {
+        assert_eq!(
+            tiles.len(),
+            (width * height) as usize,
+            "{width}x{height} tiles"
+        );
+        Grid {
+            width,
+            height,
+            tiles,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Every tile, row by row.
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    pub fn tiles_mut(&mut self) -> &mut [Tile] {
+        &mut self.tiles
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Tile]> {
+        self.tiles.chunks(self.width as usize)
+    }
+
+    pub fn contains(&self, (x, y): (u32, u32)) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// The index in [`Grid::tiles`] of a coordinate.
+    pub fn index(&self, (x, y): (u32, u32)) -> usize {
+        (x + y * self.width) as usize
+    }
+
+    /// The coordinate of an index in [`Grid::tiles`].
+    pub fn coord(&self, idx: usize) -> (u32, u32) {
+        (idx as u32 % self.width, idx as u32 / self.width)
+    }
+
+    /// The next coordinate in a direction, if it is still on the grid.
+    pub fn neighbour(&self, (x, y): (u32, u32), direction: Direction) -> Option<(u32, u32)> {
+        match direction {
+            Direction::Left if x > 0 => Some((x - 1, y)),
+            Direction::Right if x + 1 < self.width => Some((x + 1, y)),
+            Direction::Up if y > 0 => Some((x, y - 1)),
+            Direction::Down if y + 1 < self.height => Some((x, y + 1)),
+            _ => None,
+        }
+    }
+
+    /// The grid cropped or extended to `width` x `height` from its top left
+    /// corner, the new cells filled with `fill`.
+    pub fn resized(&self, width: u32, height: u32, fill: Tile) -> Grid {
+        let mut grid = Grid::new(width, height, fill);
+        for y in 0..height.min(self.height) {
+            for x in 0..width.min(self.width) {
+                grid[(x, y)] = self[(x, y)];
+            }
+        }
+        grid
+    }
+}
+
+impl Index<(u32, u32)> for Grid {
+    type Output = Tile;
+
+    fn index(&self, coord: (u32, u32)) -> &Tile {
+        &self.tiles[self.index(coord)]
+    }
+}
+
+impl IndexMut<(u32, u32)> for Grid {
+    fn index_mut(&mut self, coord: (u32, u32)) -> &mut Tile {
+        let idx = self.index(coord);
+        &mut self.tiles[idx]
+    }
+}
@@ -0,0 +1,150 @@
+//! The text files of `key = value` lines: the save file, the leaderboard,
+//! replays and pack manifests, and the settings and keys of the game.
+//!
+//! Blank lines are left out, and `#` at the start of a line or after a space
+//! starts a comment. A `#` inside a word is kept, level files may have one in
+//! their name. The key file names keys such as `#`, it only has whole-line
+//! comments, see [`lines_with_hashes`].
+
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A line of a file, trimmed and without its comment.
+#[derive(Debug, Clone, Copy)]
+pub struct Line<'a> {
+    pub text: &'a str,
+    path: &'a Path,
+    /// From 1.
+    number: usize,
+}
+
+impl<'a> Line<'a> {
+    /// `msg` prefixed by the path of the file and the number of the line.
+    pub fn error(&self, msg: impl Display) -> String {
+        format!("{}:{}: {}", self.path.display(), self.number, msg)
+    }
+
+    /// The key and the value of the line, trimmed.
+    pub fn key_value(&self) -> Result<(&'a str, &'a str), String> {
+        self.text
+            .split_once('=')
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .ok_or_else(|| self.error(format!("Expected `key = value`, got: {}", self.text)))
+    }
+}
+
+/// The lines of `content`, the text of the file at `path`, that aren't blank
+/// or a comment.
+pub fn lines<'a>(path: &'a Path, content: &'a str) -> impl Iterator<Item = Line<'a>> {
+    lines_with(path, content, strip_comment)
+}
+
+/// Like [`lines`], but only a line starting with `#` is a comment, for values
+/// that may be a `#` on its own.
+pub fn lines_with_hashes<'a>(path: &'a Path, content: &'a str) -> impl Iterator<Item = Line<'a>> {
+    lines_with(path, content, |line| {
+        if line.trim_start().starts_with('#') {
+            ""
+        } else {
+            line
+        }
+    })
+}
+
+fn lines_with<'a>(
+    path: &'a Path,
+    content: &'a str,
+    strip_comment: fn(&str) -> &str,
+) -> impl Iterator<Item = Line<'a>> {
+    content
+        .lines()
+        .enumerate()
+        .map(move |(idx, line)| Line {
+            text: strip_comment(line).trim(),
+            path,
+            number: idx + 1,
+        })
+        .filter(|line| !line.text.is_empty())
+}
+
+/// The text of the file at `path`.
+pub fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// The text of the file at `path`, `None` if there is no such file.
+pub fn read_if_exists(path: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("{}: {}", path.display(), err)),
+    }
+}
+
+/// Write `text` to the file at `path`, creating its directory if needed.
+pub fn write(path: &Path, text: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    fs::write(path, text).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// The line without its comment.
+fn strip_comment(line: &str) -> &str {
+    let mut after_space = true;
+    for (idx, c) in line.char_indices() {
+        if c == '#' && after_space {
+            return &line[..idx];
+        }
+        after_space = c.is_whitespace();
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_start_at_a_word() {
+        assert_eq!(strip_comment("# a comment"), "");
+        assert_eq!(strip_comment("name = Pack # a comment"), "name = Pack ");
+        assert_eq!(strip_comment("name = Pack\t#comment"), "name = Pack\t");
+        assert_eq!(
+            strip_comment("level = eggs level#2.blm"),
+            "level = eggs level#2.blm"
+        );
+    }
+
+    #[test]
+    fn blank_lines_and_comments_are_left_out() {
+        let path = Path::new("file.txt");
+        let content = "# first\n\n  name = value  \n   # indented\nkey=1 # last\n";
+        let lines = lines(path, content).collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].key_value(), Ok(("name", "value")));
+        assert_eq!(lines[1].key_value(), Ok(("key", "1")));
+        assert_eq!(lines[1].error("Oops"), "file.txt:5: Oops");
+    }
+
+    #[test]
+    fn hashes_may_be_values() {
+        let content = "# first\nhash = #\nkeypad = Keypad #\n";
+        let lines = lines_with_hashes(Path::new("keys.txt"), content).collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].key_value(), Ok(("hash", "#")));
+        assert_eq!(lines[1].key_value(), Ok(("keypad", "Keypad #")));
+    }
+
+    #[test]
+    fn a_line_without_a_value_is_an_error() {
+        let content = "name = value\nno value\n";
+        let line = lines(Path::new("file.txt"), content).nth(1).unwrap();
+        assert_eq!(
+            line.key_value(),
+            Err("file.txt:2: Expected `key = value`, got: no value".to_string())
+        );
+    }
+}
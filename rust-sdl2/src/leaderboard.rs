@@ -0,0 +1,201 @@
+//! Every clear of every level, best first.
+//!
+//! The leaderboard file is a text file with one `clear = ...` line per clear:
+//! the time in frames, the steps walked, the deaths, the moves undone, the
+//! date, then the level (as [`crate::Level::id`]):
+//!
+//! ```text
+//! clear = 1260 54 0 0 2024-05-01 Normal-01
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::key_value;
+use crate::progress::{data_dir, Clear};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Run {
+    pub frames: u32,
+    pub steps: u32,
+    pub deaths: u32,
+    pub undos: usize,
+    /// The day of the clear, `YYYY-MM-DD`.
+    pub date: String,
+}
+
+impl Run {
+    /// Clears without undo first, then the fastest, then the shortest.
+    fn rank_key(&self) -> (bool, u32, u32) {
+        (self.undos > 0, self.frames, self.steps)
+    }
+
+    pub fn clear(&self) -> Clear {
+        Clear {
+            frames: self.frames,
+            undos: self.undos,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Leaderboard {
+    runs: BTreeMap<String, Vec<Run>>,
+}
+
+impl Leaderboard {
+    /// `leaderboard.txt` in the [`data_dir`].
+    pub fn default_path() -> Option<PathBuf> {
+        Some(data_dir()?.join("leaderboard.txt"))
+    }
+
+    /// Read a leaderboard file, a missing one is an empty leaderboard.
+    pub fn load(path: &Path) -> Result<Leaderboard, Box<dyn std::error::Error>> {
+        match key_value::read_if_exists(path)? {
+            Some(content) => Ok(Leaderboard::parse(path, &content)?),
+            None => Ok(Leaderboard::default()),
+        }
+    }
+
+    /// Parse `content`, the text of the leaderboard file at `path`.
+    fn parse(path: &Path, content: &str) -> Result<Leaderboard, String> {
+        let mut leaderboard = Leaderboard::default();
+        for line in key_value::lines(path, content) {
+            let (key, value) = line.key_value()?;
+            if key != "clear" {
+                return Err(line.error(format!("Unknown key: {key}")));
+            }
+            let fields = value.splitn(6, ' ').collect::<Vec<_>>();
+            let [frames, steps, deaths, undos, date, level] = fields[..] else {
+                return Err(line.error(format!(
+                    "Expected `clear = <frames> <steps> <deaths> <undos> <date> <level>`, got: {value}"
+                )));
+            };
+            let number = |name: &str, value: &str| {
+                value
+                    .parse::<u32>()
+                    .map_err(|e| line.error(format!("Invalid {name} {value}: {e}")))
+            };
+            let run = Run {
+                frames: number("time", frames)?,
+                steps: number("steps", steps)?,
+                deaths: number("deaths", deaths)?,
+                undos: number("undos", undos)? as usize,
+                date: date.to_string(),
+            };
+            leaderboard.record(level.trim(), run);
+        }
+        Ok(leaderboard)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(key_value::write(path, &self.to_text())?)
+    }
+
+    /// The text of the leaderboard file.
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (level, runs) in &self.runs {
+            for run in runs {
+                text.push_str(&format!(
+                    "clear = {} {} {} {} {} {level}\n",
+                    run.frames, run.steps, run.deaths, run.undos, run.date
+                ));
+            }
+        }
+        text
+    }
+
+    /// Add a clear of `level`, returns its rank (0 for a new best).
+    pub fn record(&mut self, level: &str, run: Run) -> usize {
+        let runs = self.runs.entry(level.to_string()).or_default();
+        // a tie goes to the older clear
+        let rank = runs.partition_point(|other| other.rank_key() <= run.rank_key());
+        runs.insert(rank, run);
+        rank
+    }
+
+    /// The `count` best clears of `level`.
+    pub fn top(&self, level: &str, count: usize) -> &[Run] {
+        self.runs
+            .get(level)
+            .map_or(&[], |runs| &runs[..runs.len().min(count)])
+    }
+}
+
+/// The current day in UTC, `YYYY-MM-DD`.
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    // Days to civil date, from Howard Hinnant's `civil_from_days`
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Leaderboard, String> {
+        Leaderboard::parse(Path::new("leaderboard.txt"), content)
+    }
+
+    fn run(frames: u32, undos: usize) -> Run {
+        Run {
+            frames,
+            steps: 54,
+            deaths: 1,
+            undos,
+            date: "2024-05-01".to_string(),
+        }
+    }
+
+    #[test]
+    fn a_malformed_clear_is_an_error() {
+        let err = parse("clear = 1260 54 0 0 2024-05-01 Normal-01\nclear = 1260 54\n");
+        assert_eq!(
+            err.unwrap_err(),
+            "leaderboard.txt:2: Expected `clear = <frames> <steps> <deaths> <undos> <date> <level>`, got: 1260 54"
+        );
+        let err = parse("clear = 12.5 54 0 0 2024-05-01 Normal-01\n").unwrap_err();
+        assert!(
+            err.starts_with("leaderboard.txt:1: Invalid time 12.5"),
+            "{err}"
+        );
+        let err = parse("clear 1260 54 0 0 2024-05-01 Normal-01\n").unwrap_err();
+        assert!(
+            err.starts_with("leaderboard.txt:1: Expected `key = value`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn an_unknown_key_is_an_error() {
+        assert_eq!(
+            parse("# comment\nbest = Normal-01 1260 0\n").unwrap_err(),
+            "leaderboard.txt:2: Unknown key: best"
+        );
+    }
+
+    #[test]
+    fn saved_leaderboards_load_back() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.record("Normal-01", run(1260, 0));
+        leaderboard.record("Normal-01", run(900, 2));
+        leaderboard.record("packs/my levels/one.blm", run(3000, 0));
+        let loaded = parse(&leaderboard.to_text()).unwrap();
+        assert_eq!(loaded.top("Normal-01", 5), [run(1260, 0), run(900, 2)]);
+        assert_eq!(loaded.top("packs/my levels/one.blm", 5), [run(3000, 0)]);
+    }
+}
@@ -0,0 +1,45 @@
+//! Headless game rules of Bobby Carrot.
+//!
+//! The SDL binary, tools and bots all drive the same [`GameState`], which
+//! knows nothing about rendering or timers: every call to
+//! [`GameState::step`] advances the simulation by exactly one frame.
+
+mod bobby;
+mod editor;
+mod game;
+mod generate;
+mod grid;
+pub mod key_value;
+mod leaderboard;
+mod map;
+mod pack;
+mod progress;
+mod replay;
+pub mod smaf;
+mod solver;
+pub mod text;
+mod tile;
+mod undo;
+mod validate;
+
+pub use bobby::{Bobby, State};
+pub use editor::Editor;
+pub use game::{Direction, GameEvent, GameState, KeyColor};
+pub use generate::{generate, GenerateOptions, Generated, Mechanics, MAX_DIFFICULTY};
+pub use grid::Grid;
+pub use leaderboard::{today, Leaderboard, Run};
+pub use map::{Goal, Map, MapInfo, BLM_HEADER, BLM_VERSION, EGG_LEVELS, NORMAL_LEVELS};
+pub use pack::{Level, LevelPack, LevelSource};
+pub use progress::{config_dir, data_dir, Clear, Progress};
+pub use replay::{Input, Replay};
+pub use solver::{solve, solve_cancellable, solve_with_limit, Solution};
+pub use tile::{Collectible, SwitchColor, Tile};
+pub use undo::{Snapshot, UndoStack};
+pub use validate::{validate, Problem};
+
+pub const FRAMES: u64 = 60;
+pub const FRAMES_PER_STEP: u32 = 2;
+/// Size in tiles of the original levels, the ones of the version 1 `.blm`
+/// files.
+pub const WIDTH_POINTS: u32 = 16;
+pub const HEIGHT_POINTS: u32 = 16;
@@ -1,223 +1,574 @@
+mod audio;
+mod bindings;
+mod camera;
+mod controller;
+mod display;
+mod edit;
+mod menu;
+mod pause;
+mod render;
+mod settings;
+
 use std::env;
-use std::fmt;
-use std::thread::sleep;
-use std::time::Duration;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+use audio::{Audio, Sound, VOLUME_STEP};
+use bindings::{Action, Bindings};
+use bobby_carrot::{
+    key_value, text, today, Bobby, Direction, GameEvent, GameState, GenerateOptions, Goal, Grid,
+    Input, Leaderboard, LevelPack, Map, MapInfo, Mechanics, Progress, Replay, Run, Solution, State,
+    Tile, UndoStack, FRAMES, FRAMES_PER_STEP, MAX_DIFFICULTY,
+};
+use camera::Camera;
+use controller::Controllers;
+use display::{Display, WindowOptions};
+use menu::{Choice, Menu};
+use pause::{PauseChoice, PauseMenu};
 use sdl2::{
-    event::Event,
+    event::{Event, WindowEvent},
+    gfx::primitives::DrawRenderer,
     image::LoadTexture,
-    keyboard::{Keycode, Scancode},
+    keyboard::Keycode,
     pixels::Color,
     rect::Rect,
-    render::{BlendMode, Texture, TextureCreator},
+    render::{Canvas, RenderTarget, Texture, TextureCreator},
 };
+use settings::Settings;
 
-const FRAMES: u64 = 60;
-const FRAMES_PER_STEP: u32 = 2;
-const WIDTH_POINTS: u32 = 16;
-const HEIGHT_POINTS: u32 = 16;
 const VIEW_WIDTH_POINTS: u32 = 10;
 const VIEW_HEIGHT_POINTS: u32 = 12;
 
-const MS_PER_FRAME: u64 = 1000 / FRAMES;
-const WIDTH: u32 = 32 * WIDTH_POINTS;
-const HEIGHT: u32 = 32 * HEIGHT_POINTS;
+/// States explored by `solve` before giving up, unless `--max-states` is set.
+const SOLVE_MAX_STATES: usize = 5_000_000;
+
+/// States explored for a hint before giving up, kept low so that the answer
+/// comes quickly.
+const HINT_MAX_STATES: usize = 1_000_000;
+
+/// Simulated time of one [`GameState::step`].
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / FRAMES);
+/// Frames between two undos while the rewind key is held.
+const REWIND_FRAMES: u32 = 8;
+/// After a longer hiccup the game slows down instead of catching up in one go.
+const MAX_LAG: Duration = Duration::from_millis(250);
 const VIEW_WIDTH: u32 = 32 * VIEW_WIDTH_POINTS;
 const VIEW_HEIGHT: u32 = 32 * VIEW_HEIGHT_POINTS;
-const WIDTH_POINTS_DELTA: u32 = WIDTH_POINTS - VIEW_WIDTH_POINTS;
-const HEIGHT_POINTS_DELTA: u32 = HEIGHT_POINTS - VIEW_HEIGHT_POINTS;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut map = Map::Normal(1);
-    let args = env::args().collect::<Vec<_>>();
-    if args.len() > 1 {
-        let arg = &args[1];
-        let (type_str, num_str) = arg
-            .split_once('-')
-            .ok_or_else(|| format!("Invalid map: {arg}"))?;
-        let num: u32 = num_str.parse()?;
-        match type_str {
-            "normal" => map = Map::Normal(num),
-            "egg" => map = Map::Egg(num),
-            _ => return Err(format!("Invalid map: {arg}").into()),
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("convert") => return convert(&args.skip(1).collect::<Vec<_>>()),
+        Some("validate") => return validate(&args.skip(1).collect::<Vec<_>>()),
+        Some("solve") => return solve(&args.skip(1).collect::<Vec<_>>()),
+        Some("generate") => return generate(&args.skip(1).collect::<Vec<_>>()),
+        Some("edit") => return edit::edit(&args.skip(1).collect::<Vec<_>>()),
+        Some("render") => return render::render(&args.skip(1).collect::<Vec<_>>()),
+        _ => {}
+    }
+
+    let progress_path = Progress::default_path();
+    let mut progress = match &progress_path {
+        Some(path) => Progress::load(path)?,
+        None => Progress::default(),
+    };
+    let leaderboard_path = Leaderboard::default_path();
+    let mut leaderboard = match &leaderboard_path {
+        Some(path) => Leaderboard::load(path)?,
+        None => Leaderboard::default(),
+    };
+
+    let mut pack = LevelPack::normal();
+    let mut pack_path = None;
+    let mut level = 0;
+    let mut playback = None;
+    let mut record_path = None;
+    let mut volume = 100;
+    let mut window_options = WindowOptions::default();
+    // Without a level to play, the game opens on the menu, at the last one
+    let mut resume = true;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--level" => {
+                let path = args.next().ok_or("Missing level file after --level")?;
+                pack = LevelPack::single(&normalize(&path))?;
+                pack_path = None;
+                level = 0;
+                resume = false;
+            }
+            "--pack" => {
+                let path = args.next().ok_or("Missing manifest file after --pack")?;
+                let path = normalize(&path);
+                pack = LevelPack::load(&path)?;
+                pack_path = Some(path);
+                level = 0;
+                resume = false;
+            }
+            "--replay" => {
+                let path = args.next().ok_or("Missing replay file after --replay")?;
+                let replay = Replay::load(Path::new(&path))?;
+                if replay.is_outdated() {
+                    eprintln!(
+                        "{path}: recorded with version {}, it may not play back the same",
+                        replay.version
+                    );
+                }
+                (pack, level) = find_pack_level(&replay.level, replay.pack.as_deref())?;
+                pack_path = replay.pack.clone();
+                playback = Some(replay);
+                resume = false;
+            }
+            "--volume" => {
+                let value = args.next().ok_or("Missing volume after --volume")?;
+                volume = value
+                    .parse()
+                    .map_err(|err| format!("Invalid --volume {value}: {err}"))?;
+            }
+            "--scale" => {
+                window_options.scale = Some(parse_scale(args.next())?);
+            }
+            "--fullscreen" => window_options.fullscreen = true,
+            "--record" => {
+                let path = args.next().ok_or("Missing replay file after --record")?;
+                record_path = Some(PathBuf::from(path));
+            }
+            _ => {
+                (pack, level) = LevelPack::containing(arg.parse()?)?;
+                pack_path = None;
+                resume = false;
+            }
         }
     }
-    let mut map_info_fresh = map.load_map_info()?;
-    let mut map_info = map_info_fresh.clone();
+    if resume {
+        if let Some(last) = &progress.last_level {
+            match find_pack_level(last, progress.last_pack.as_deref()) {
+                Ok((last_pack, last_level)) => {
+                    (pack, level) = (last_pack, last_level);
+                    pack_path = progress.last_pack.clone();
+                }
+                Err(err) => eprintln!("Can't resume from {last}: {err}"),
+            }
+        }
+    }
+    // A replay doesn't count as a clear
+    let mut replayed = playback.is_some();
+    progress.last_level = Some(pack.levels[level].id());
+    progress.last_pack = pack_path.clone();
+    save_data(progress_path.as_deref(), |path| progress.save(path));
+    let mut recording = Replay::new(&pack.levels[level].id(), pack_path.as_deref());
+    let mut game = GameState::new(pack.levels[level].load_map_info()?);
 
     let context = sdl2::init()?;
     let video_subsystem = context.video()?;
-    let timer = context.timer()?;
-
-    #[cfg(target_os = "linux")]
-    let scale = 2.0;
-    #[cfg(not(target_os = "linux"))]
-    let scale = 1.0;
+    // Key names are looked up through SDL, once it is initialized
+    let bindings_path = Bindings::default_path();
+    let mut bindings = match &bindings_path {
+        Some(path) => Bindings::load(path)?,
+        None => Bindings::default(),
+    };
+    report_conflicts(&bindings);
+    let settings_path = Settings::default_path();
+    let mut settings = match &settings_path {
+        Some(path) => Settings::load(path)?,
+        None => Settings::default(),
+    };
+    let mut camera = Camera::new(settings.camera);
 
     let mut show_help = false;
     let mut full_view = false;
-    let window = video_subsystem
-        .window(
-            format!("Bobby Carrot ({})", map).as_str(),
-            (VIEW_WIDTH as f32 * scale) as u32,
-            (VIEW_HEIGHT as f32 * scale) as u32,
-        )
-        .build()?;
-    let mut canvas = window.into_canvas().present_vsync().build()?;
-    canvas.set_scale(scale, scale)?;
-    canvas.set_blend_mode(BlendMode::Blend);
+    let (mut display, mut canvas) = Display::open(
+        &video_subsystem,
+        format!("Bobby Carrot ({})", pack.levels[level].name).as_str(),
+        VIEW_WIDTH,
+        VIEW_HEIGHT,
+        window_options,
+    )?;
     let texture_creator = canvas.texture_creator();
     let mut event_pump = context.event_pump()?;
 
-    let mut frame: u32 = 0;
     let assets = Assets::load_all(&texture_creator)?;
-    let mut bobby = Bobby::new(frame, timer.ticks(), map_info.coord_start);
+    let mut audio = Audio::open(&context, volume);
+    let mut controllers = Controllers::open(&context);
+    let mut menu = Menu::new(&pack, pack_path.clone(), level);
+    let mut show_menu = resume;
+    let mut pause = PauseMenu::new();
+    let mut paused = false;
+    if show_menu {
+        audio.play(Sound::Title);
+    }
+    // The HUD clock counts frames, so that it follows the speed of the game
+    let mut start_frame = game.frame();
+    let mut hints = Hints::default();
+    let mut undo = UndoStack::new();
+    // Counted for the leaderboard since the level started, deaths included
+    let mut steps = 0;
+    let mut deaths = 0;
+    let mut results: Option<Results> = None;
+    let mut inputs = Vec::new();
+    let mut next_level = None;
+    let mut last_instant = Instant::now();
+    let mut lag = Duration::ZERO;
+    // Where Bobby was drawn before the last step, the frames drawn until the
+    // next one move him from there to where he is now
+    let mut previous_bobby = None;
 
     'running: loop {
-        let now_ms = timer.ticks();
+        let now = Instant::now();
+        let elapsed = now - last_instant;
+        lag = (lag + elapsed).min(MAX_LAG);
+        last_instant = now;
+        if show_menu {
+            for event in event_pump.poll_iter() {
+                controllers.handle(&event);
+                let event = display.to_view(controller::as_key(event, controller::menu_key));
+                if let Event::Quit { .. } = event {
+                    break 'running;
+                }
+                match menu.handle(&event, &progress, &mut bindings) {
+                    Some(Choice::Play(menu_pack, menu_pack_path, menu_level)) => {
+                        pack = menu_pack;
+                        pack_path = menu_pack_path;
+                        next_level = Some(menu_level);
+                        show_menu = false;
+                        audio.stop_music();
+                        break;
+                    }
+                    Some(Choice::Resume) => {
+                        show_menu = false;
+                        paused = true;
+                        audio.stop_music();
+                        break;
+                    }
+                    Some(Choice::KeysChanged) => {
+                        report_conflicts(&bindings);
+                        save_data(bindings_path.as_deref(), |path| bindings.save(path));
+                    }
+                    Some(Choice::Fullscreen) => display.toggle_fullscreen(&mut canvas)?,
+                    Some(Choice::Quit) => break 'running,
+                    None => {}
+                }
+            }
+            if show_menu {
+                display.begin_frame(&mut canvas, VIEW_WIDTH, VIEW_HEIGHT)?;
+                menu.draw(
+                    &mut canvas,
+                    &assets.title_texture,
+                    assets.logo_texture.as_ref(),
+                    &progress,
+                    &bindings,
+                )?;
+                display.present(&mut canvas);
+                // The game doesn't run behind the menu
+                lag = Duration::ZERO;
+                continue;
+            }
+        }
         for event in event_pump.poll_iter() {
+            controllers.handle(&event);
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Q),
+                Event::Quit { .. } => break 'running,
+                // nobody is looking at the game anymore
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
                     ..
-                } => break 'running,
+                } if !paused && results.is_none() => {
+                    pause.open();
+                    paused = true;
+                    continue;
+                }
+                _ => {}
+            }
+            if paused {
+                let event = display.to_view(controller::as_key(event, controller::menu_key));
+                let view = view_size(&game.map_info().data, full_view);
+                match pause.handle(&event, &mut settings, &bindings, view) {
+                    Some(PauseChoice::Resume) => paused = false,
+                    Some(PauseChoice::Restart) => {
+                        // nothing is restarted under the results panel
+                        if results.is_none() {
+                            inputs.push(Input::Restart);
+                        }
+                        paused = false;
+                    }
+                    Some(PauseChoice::Levels) => {
+                        menu.show_levels_from_pause(&pack.levels[level].id());
+                        show_menu = true;
+                        audio.play(Sound::Title);
+                        paused = false;
+                    }
+                    Some(PauseChoice::SettingsChanged) => {
+                        camera.settings = settings.camera;
+                        save_data(settings_path.as_deref(), |path| settings.save(path));
+                    }
+                    Some(PauseChoice::Quit) => break 'running,
+                    None => {}
+                }
+                continue;
+            }
+            let (action, confirm) = match event {
                 Event::KeyDown {
                     keycode: Some(code),
                     ..
                 } => {
-                    if code != Keycode::H && code != Keycode::F1 {
-                        show_help = false;
-                    }
-                    match code {
-                        Keycode::R => {
-                            map_info = map_info_fresh.clone();
-                            bobby = Bobby::new(frame, now_ms, map_info.coord_start);
-                        }
-                        Keycode::N => {
-                            map = map.next();
-                            canvas
-                                .window_mut()
-                                .set_title(format!("Bobby Carrot ({})", map).as_str())?;
-                            map_info_fresh = map.load_map_info()?;
-                            map_info = map_info_fresh.clone();
-                            bobby = Bobby::new(frame, now_ms, map_info.coord_start);
-                        }
-                        Keycode::P => {
-                            map = map.previous();
-                            canvas
-                                .window_mut()
-                                .set_title(format!("Bobby Carrot ({})", map).as_str())?;
-                            map_info_fresh = map.load_map_info()?;
-                            map_info = map_info_fresh.clone();
-                            bobby = Bobby::new(frame, now_ms, map_info.coord_start);
-                        }
-                        Keycode::F => {
-                            full_view = !full_view;
-                            if full_view {
-                                canvas.window_mut().set_size(WIDTH, HEIGHT)?;
-                            } else {
-                                canvas.window_mut().set_size(VIEW_WIDTH, VIEW_HEIGHT)?;
-                            }
-                        }
-                        Keycode::H | Keycode::F1 => {
-                            show_help = !show_help;
-                        }
-                        _ => {}
-                    }
+                    let action = bindings.action(code);
+                    let confirm =
+                        matches!(code, Keycode::Return | Keycode::KpEnter | Keycode::Space);
+                    (action, confirm || action == Some(Action::Next))
+                }
+                Event::ControllerButtonDown { button, .. } => {
+                    let action = controller::game_action(button);
+                    (action, action == Some(Action::Next))
+                }
+                _ => continue,
+            };
+            if let (Some(results), true) = (&mut results, confirm) {
+                // the last level of the pack is followed by the ending
+                if pack.next(level) == 0 && !results.ending {
+                    results.ending = true;
+                } else {
+                    menu.show_levels(&pack.levels[pack.next(level)].id());
+                    show_menu = true;
+                    audio.play(Sound::Title);
+                }
+                continue;
+            }
+            let Some(action) = action else {
+                continue;
+            };
+            // The level is over, it only goes on to the next one
+            if results.is_some() && matches!(action, Action::Restart | Action::Undo | Action::Pause)
+            {
+                continue;
+            }
+            if action != Action::Help {
+                show_help = false;
+            }
+            match action {
+                Action::Quit => break 'running,
+                Action::Menu => {
+                    menu.show_levels(&pack.levels[level].id());
+                    show_menu = true;
+                    audio.play(Sound::Title);
+                }
+                Action::Restart => inputs.push(Input::Restart),
+                Action::Undo => inputs.push(Input::Undo),
+                Action::Next => next_level = Some(pack.next(level)),
+                Action::Previous => next_level = Some(pack.previous(level)),
+                Action::FullView => {
+                    full_view = !full_view;
+                    let (width, height) = view_size(&game.map_info().data, full_view);
+                    display.resize(&mut canvas, width, height)?;
+                    camera.snap();
                 }
+                Action::Fullscreen => display.toggle_fullscreen(&mut canvas)?,
+                Action::Help => {
+                    show_help = !show_help;
+                }
+                Action::Hints => {
+                    hints.enabled = !hints.enabled;
+                }
+                Action::Pause => {
+                    pause.open();
+                    paused = true;
+                }
+                Action::Mute => audio.toggle_mute(),
+                Action::VolumeDown => audio.change_volume(-(VOLUME_STEP as i32)),
+                Action::VolumeUp => audio.change_volume(VOLUME_STEP as i32),
                 _ => {}
             }
         }
-        let keyboard = event_pump.keyboard_state();
-        let is_pressed = |code| keyboard.is_scancode_pressed(code);
-        let state_opt = if is_pressed(Scancode::Left) || is_pressed(Scancode::A) {
-            Some(State::Left)
-        } else if is_pressed(Scancode::Right) || is_pressed(Scancode::D) {
-            Some(State::Right)
-        } else if is_pressed(Scancode::Up) || is_pressed(Scancode::W) {
-            Some(State::Up)
-        } else if is_pressed(Scancode::Down) || is_pressed(Scancode::S) {
-            Some(State::Down)
-        } else {
-            None
-        };
-        if let Some(state) = state_opt {
-            bobby.last_action_time = now_ms;
-            if !bobby.is_walking() {
-                bobby.update_state(state, frame, &map_info.data);
-            } else {
-                bobby.update_next_state(state, frame);
+        if let Some(next_level) = next_level.take() {
+            if let Some(path) = &record_path {
+                save_recording(path, &recording)?;
+            }
+            level = next_level;
+            canvas
+                .window_mut()
+                .set_title(format!("Bobby Carrot ({})", pack.levels[level].name).as_str())?;
+            game = GameState::new(pack.levels[level].load_map_info()?);
+            if full_view {
+                let (width, height) = view_size(&game.map_info().data, full_view);
+                display.resize(&mut canvas, width, height)?;
             }
+            recording = Replay::new(&pack.levels[level].id(), pack_path.as_deref());
+            playback = None;
+            replayed = false;
+            undo = UndoStack::new();
+            steps = 0;
+            deaths = 0;
+            results = None;
+            progress.last_level = Some(pack.levels[level].id());
+            progress.last_pack = pack_path.clone();
+            save_data(progress_path.as_deref(), |path| progress.save(path));
+            hints.invalidate();
+            camera.snap();
+            previous_bobby = None;
+            start_frame = game.frame();
         }
+        // The game and its clock stand still behind the pause menu and the
+        // help
+        if paused || show_help {
+            lag = Duration::ZERO;
+        }
+        // Fixed steps of simulation for the time that passed, then one render
+        while lag >= TICK {
+            lag -= TICK;
+            if let Some(replay) = &playback {
+                // The keyboard is ignored while a replay plays
+                inputs = replay.inputs_at(game.frame()).collect();
+                if game.frame() >= replay.end_frame() {
+                    playback = None;
+                }
+            } else if results.is_none() {
+                let keyboard = event_pump.keyboard_state();
+                let is_held = |action| bindings.is_held(action, &keyboard);
+                let direction = bindings
+                    .held_direction(&keyboard)
+                    .or_else(|| controllers.direction());
+                if is_held(Action::Rewind) && game.frame().is_multiple_of(REWIND_FRAMES) {
+                    inputs.push(Input::Undo);
+                }
+                inputs.extend(direction.map(Input::Hold));
+            }
 
-        // Finished and hit the end position
-        if bobby.dead {
-            map_info_fresh = map.load_map_info()?;
-            map_info = map_info_fresh.clone();
-            bobby = Bobby::new(frame, now_ms, map_info.coord_start);
-        } else if bobby.is_finished(&map_info)
-            && map_info.data[(bobby.coord_src.0 + bobby.coord_src.1 * 16) as usize] == 44
-        {
-            if bobby.faded_out {
-                map = map.next();
-                canvas
-                    .window_mut()
-                    .set_title(format!("Bobby Carrot ({})", map).as_str())?;
-                map_info_fresh = map.load_map_info()?;
-                map_info = map_info_fresh.clone();
-                bobby = Bobby::new(frame, now_ms, map_info.coord_start);
-            } else if bobby.state != State::FadeOut {
-                bobby.start_frame = frame;
-                bobby.state = State::FadeOut;
+            let mut direction = None;
+            for input in inputs.drain(..) {
+                if !game.is_cleared() {
+                    recording.record(game.frame(), input);
+                }
+                match input {
+                    Input::Restart => {
+                        game.restart();
+                        undo = UndoStack::new();
+                        steps = 0;
+                        deaths = 0;
+                        hints.invalidate();
+                        start_frame = game.frame();
+                    }
+                    Input::Undo => {
+                        if undo.undo(&mut game) {
+                            hints.invalidate();
+                        }
+                    }
+                    Input::Hold(held) => direction = Some(held),
+                }
+            }
+            previous_bobby = Some(bobby_rects(game.bobby(), game.frame(), None, 0.0).1);
+            let events = game.step(direction);
+            undo.update(&game, &events);
+            for event in events {
+                match event {
+                    GameEvent::Moved { .. } => {
+                        steps += 1;
+                        hints.invalidate();
+                    }
+                    GameEvent::CarrotCollected | GameEvent::EggCollected => {
+                        audio.play(Sound::Carrot)
+                    }
+                    GameEvent::LockOpened(_) => audio.play(Sound::Lock),
+                    GameEvent::Died => {
+                        deaths += 1;
+                        audio.play(Sound::Death);
+                    }
+                    GameEvent::Finished => {
+                        // the end of the pack has its own tune
+                        if pack.next(level) == 0 {
+                            audio.play(Sound::End);
+                        } else {
+                            audio.play(Sound::Cleared);
+                        }
+                    }
+                    GameEvent::Respawned => {
+                        hints.invalidate();
+                        start_frame = game.frame();
+                    }
+                    GameEvent::LevelCleared => {
+                        let run = Run {
+                            frames: game.frame() - start_frame,
+                            steps,
+                            deaths,
+                            undos: undo.undos(),
+                            date: today(),
+                        };
+                        let mut rank = None;
+                        if !replayed {
+                            let id = pack.levels[level].id();
+                            progress.record_clear(&id, run.clear());
+                            rank = Some(leaderboard.record(&id, run.clone()));
+                            save_data(progress_path.as_deref(), |path| progress.save(path));
+                            save_data(leaderboard_path.as_deref(), |path| leaderboard.save(path));
+                        }
+                        results = Some(Results {
+                            run,
+                            rank,
+                            ending: false,
+                        });
+                    }
+                    _ => {}
+                }
             }
-        } else if now_ms - bobby.last_action_time >= 4000
-            && !bobby.is_walking()
-            && bobby.state != State::Idle
-            && bobby.state != State::Death
-            && bobby.state != State::FadeIn
-            && bobby.state != State::FadeOut
-            && bobby.next_state.is_none()
-        {
-            bobby.start_frame = frame;
-            bobby.state = State::Idle;
         }
+        hints.update(&game);
 
-        let (bobby_src, bobby_dest) = bobby.update_texture_position(frame, &mut map_info.data);
-        let bobby_texture = match bobby.state {
-            State::Idle => &assets.bobby_idle_texture,
-            State::Death => &assets.bobby_death_texture,
-            State::FadeIn => &assets.bobby_fade_texture,
-            State::FadeOut => &assets.bobby_fade_texture,
-            State::Left => &assets.bobby_left_texture,
-            State::Right => &assets.bobby_right_texture,
-            State::Up => &assets.bobby_up_texture,
-            State::Down => &assets.bobby_down_texture,
+        // How far the rendering is between the step before and the last one
+        let alpha = lag.as_secs_f32() / TICK.as_secs_f32();
+        let frame = game.frame();
+        let bobby = game.bobby();
+        let map_info = game.map_info();
+        let (view_width, view_height) = view_size(&map_info.data, full_view);
+        display.begin_frame(&mut canvas, view_width, view_height)?;
+        // Set view port, the map is drawn from its top left corner on
+        let (x_offset, y_offset) = if full_view {
+            (0, 0)
+        } else {
+            // the middle of the tile Bobby is drawn on
+            let (_, dest) = bobby_rects(bobby, frame, previous_bobby, alpha);
+            let target = (dest.x() + dest.width() as i32 / 2, dest.bottom() - 16);
+            let (x, y) = camera.update(
+                target,
+                bobby.state.direction(),
+                elapsed,
+                (view_width, view_height),
+                (32 * map_info.data.width(), 32 * map_info.data.height()),
+            );
+            display.set_camera(&mut canvas, x, y);
+            (x, y)
         };
-        let finished = bobby.is_finished(&map_info);
 
-        canvas.clear();
+        draw_tiles(
+            &mut canvas,
+            &assets,
+            &map_info.data,
+            frame,
+            game.is_finished(),
+        )?;
+        draw_bobby(&mut canvas, &assets, bobby, frame, previous_bobby, alpha)?;
 
-        for x in 0..WIDTH_POINTS {
-            for y in 0..HEIGHT_POINTS {
-                let item = map_info.data[x as usize + y as usize * 16] as i32;
-                let texture = match item {
-                    44 if finished => &assets.tile_finish_texture,
-                    40 => &assets.tile_conveyor_left_texture,
-                    41 => &assets.tile_conveyor_right_texture,
-                    42 => &assets.tile_conveyor_up_texture,
-                    43 => &assets.tile_conveyor_down_texture,
-                    _ => &assets.tileset_texture,
-                };
-                let src = if (item == 44 && finished) || (40..44).contains(&item) {
-                    Rect::new(32 * ((frame as i32 / (FRAMES as i32 / 10)) % 4), 0, 32, 32)
-                } else {
-                    Rect::new(32 * (item % 8), 32 * (item / 8), 32, 32)
+        // Hint arrow on the next tile to step to
+        if let Some(Some(Solution::Solved(moves))) = hints.current() {
+            if let Some(direction) = moves.first() {
+                let (x, y) = bobby.coord_src;
+                let (texture, dx, dy, w, h) = match direction {
+                    Direction::Left => (&assets.arrow_left_texture, -1, 0, 10, 18),
+                    Direction::Right => (&assets.arrow_right_texture, 1, 0, 10, 18),
+                    Direction::Up => (&assets.arrow_up_texture, 0, -1, 18, 10),
+                    Direction::Down => (&assets.arrow_down_texture, 0, 1, 18, 10),
                 };
                 canvas.copy_ex(
                     texture,
-                    Some(src),
-                    Some(Rect::new(32 * x as i32, 32 * y as i32, 32, 32)),
+                    None,
+                    Some(Rect::new(
+                        (x as i32 + dx) * 32 + 16 - w as i32 / 2,
+                        (y as i32 + dy) * 32 + 16 - h as i32 / 2,
+                        w,
+                        h,
+                    )),
                     0.0,
                     None,
                     false,
@@ -225,188 +576,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 )?;
             }
         }
-        canvas.copy_ex(
-            bobby_texture,
-            Some(bobby_src),
-            Some(bobby_dest),
-            0.0,
-            None,
-            false,
-            false,
-        )?;
-
-        // Set view port
-        let (x_offset, x_right_offset, y_offset) = if full_view {
-            (0, 0, 0)
-        } else {
-            let step = (frame - bobby.start_frame) as i32;
-            let x0 = bobby.coord_src.0 as i32 * 32;
-            let y0 = bobby.coord_src.1 as i32 * 32;
-            let x1 = bobby.coord_dest.0 as i32 * 32;
-            let y1 = bobby.coord_dest.1 as i32 * 32;
-            let mut x = if bobby.state == State::Death {
-                // death happened at 6/8 of walking
-                (x1 - x0) * 6 / 8 + x0 - (VIEW_WIDTH_POINTS as i32 / 2) * 32
-            } else {
-                (x1 - x0) * step / (8 * FRAMES_PER_STEP as i32) + x0
-                    - (VIEW_WIDTH_POINTS as i32 / 2) * 32
-            };
-            let mut y = if bobby.state == State::Death {
-                // death happened at 6/8 of walking
-                (y1 - y0) * 6 / 8 + y0 - (VIEW_HEIGHT_POINTS as i32 / 2) * 32
-            } else {
-                (y1 - y0) * step / (8 * FRAMES_PER_STEP as i32) + y0
-                    - (VIEW_HEIGHT_POINTS as i32 / 2) * 32
-            };
-            x += 16;
-            y += 16;
-            if x < 0 {
-                x = 0;
-            }
-            if x > WIDTH_POINTS_DELTA as i32 * 32 {
-                x = WIDTH_POINTS_DELTA as i32 * 32;
-            }
-            if y < 0 {
-                y = 0;
-            }
-            if y > HEIGHT_POINTS_DELTA as i32 * 32 {
-                y = HEIGHT_POINTS_DELTA as i32 * 32;
-            }
-            canvas.set_viewport(Rect::new(
-                -x,
-                -y,
-                VIEW_WIDTH + x as u32,
-                VIEW_HEIGHT + y as u32,
-            ));
-            (x, 32 * WIDTH_POINTS_DELTA as i32 - x, y)
-        };
 
-        // Indicator
-        let (icon_width, num_left) = if map_info.carrot_total > 0 {
-            canvas.copy_ex(
-                &assets.hud_texture,
-                Some(Rect::new(0, 0, 46, 44)),
-                Some(Rect::new(
-                    32 * 16 - (46 + 4) - x_right_offset,
-                    4 + y_offset,
-                    46,
-                    44,
-                )),
-                0.0,
-                None,
-                false,
-                false,
-            )?;
-            (46, map_info.carrot_total - bobby.carrot_count)
-        } else {
-            canvas.copy_ex(
-                &assets.hud_texture,
-                Some(Rect::new(46, 0, 34, 44)),
-                Some(Rect::new(
-                    32 * 16 - (34 + 4) - x_right_offset,
-                    4 + y_offset,
-                    34,
-                    44,
-                )),
-                0.0,
-                None,
-                false,
-                false,
-            )?;
-            (34, map_info.egg_total - bobby.egg_count)
-        };
-        let num_10 = num_left as i32 / 10;
-        let num_01 = num_left as i32 % 10;
-        canvas.copy_ex(
-            &assets.numbers_texture,
-            Some(Rect::new(num_01 * 12, 0, 12, 18)),
-            Some(Rect::new(
-                32 * 16 - (icon_width + 4) - 2 - 12 - x_right_offset,
-                4 + 14 + y_offset,
-                12,
-                18,
-            )),
-            0.0,
-            None,
-            false,
-            false,
-        )?;
-        canvas.copy_ex(
-            &assets.numbers_texture,
-            Some(Rect::new(num_10 * 12, 0, 12, 18)),
-            Some(Rect::new(
-                32 * 16 - (icon_width + 4) - 2 - 12 * 2 - 1 - x_right_offset,
-                4 + 14 + y_offset,
-                12,
-                18,
-            )),
-            0.0,
-            None,
-            false,
-            false,
+        draw_hud(
+            &mut canvas,
+            &assets,
+            map_info,
+            bobby,
+            frame - start_frame,
+            undo.undos(),
+            Rect::new(x_offset, y_offset, view_width, view_height),
         )?;
 
-        // Key
-        let mut keys = Vec::new();
-        for _ in 0..bobby.key_gray {
-            keys.push((122, keys.len() as i32));
-        }
-        for _ in 0..bobby.key_yellow {
-            keys.push((122 + 22, keys.len() as i32));
-        }
-        for _ in 0..bobby.key_red {
-            keys.push((122 + 22 + 22, keys.len() as i32));
-        }
-        for (offset, count) in keys {
-            canvas.copy_ex(
-                &assets.hud_texture,
-                Some(Rect::new(offset, 0, 22, 44)),
-                Some(Rect::new(
-                    32 * 16 - (22 + 4) - count * 22 - x_right_offset,
-                    4 + 44 + 2 + y_offset,
-                    22,
-                    44,
-                )),
-                0.0,
-                None,
-                false,
-                false,
-            )?;
-        }
-
-        // time passed
-        let passed_secs = ((now_ms - bobby.start_time) / 1000) as i32;
-        let mut minutes = passed_secs / 60;
-        let mut seconds = passed_secs % 60;
-        if minutes > 99 {
-            minutes = 99;
-            seconds = 99;
-        }
-        for (idx, offset) in [minutes / 10, minutes % 10, 10, seconds / 10, seconds % 10]
-            .into_iter()
-            .enumerate()
-        {
-            canvas.copy_ex(
-                &assets.numbers_texture,
-                Some(Rect::new(offset * 12, 0, 12, 18)),
-                Some(Rect::new(
-                    4 + 12 * idx as i32 + x_offset,
-                    4 + y_offset,
-                    12,
-                    18,
-                )),
-                0.0,
-                None,
-                false,
-                false,
-            )?;
-        }
-
         // Show help page
         if show_help {
             canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
             canvas.fill_rect(Rect::new(
-                (32 * 16 - x_offset - x_right_offset - 158) / 2 + x_offset,
+                (view_width as i32 - 158) / 2 + x_offset,
                 32 * 3 - (160 - 142) / 2 + y_offset,
                 158,
                 160,
@@ -415,7 +600,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 &assets.help_texture,
                 Some(Rect::new(0, 0, 133, 142)),
                 Some(Rect::new(
-                    (32 * 16 - x_offset - x_right_offset - 133) / 2 + x_offset,
+                    (view_width as i32 - 133) / 2 + x_offset,
                     32 * 3 + y_offset,
                     133,
                     142,
@@ -427,15 +612,537 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             )?;
         }
 
-        canvas.present();
+        // Hint message, when there is no arrow to show
+        let hint_message = match hints.current() {
+            Some(Some(Solution::Unsolvable { .. })) => Some("No way to finish, press R"),
+            Some(None) => Some("No hint found"),
+            _ => None,
+        };
+        if let Some(message) = hint_message {
+            let width = 8 * message.len() as i32 + 8;
+            let x = (view_width as i32 - width) / 2 + x_offset;
+            let y = view_height as i32 - 32 + y_offset;
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+            canvas.fill_rect(Rect::new(x, y, width as u32, 16))?;
+            canvas.string(
+                (x + 4) as i16,
+                (y + 4) as i16,
+                message,
+                Color::RGB(255, 255, 255),
+            )?;
+        }
+
+        // Results of the level just cleared
+        if let Some(Results {
+            run,
+            rank,
+            ending: false,
+        }) = &results
+        {
+            let id = pack.levels[level].id();
+            let mut lines = vec![
+                (format!("{} cleared", pack.levels[level].name), false),
+                (
+                    format!("Time {}  Steps {}", format_time(run.frames), run.steps),
+                    false,
+                ),
+                (format!("Deaths {}  Undos {}", run.deaths, run.undos), false),
+            ];
+            if *rank == Some(0) {
+                lines.push(("New personal best!".to_string(), true));
+            }
+            lines.push((String::new(), false));
+            lines.push((
+                format!(
+                    "{:>2} {:<5} {:>4} {:>3} {:>3} {}",
+                    "#", "time", "step", "die", "und", "date"
+                ),
+                false,
+            ));
+            for (idx, run) in leaderboard.top(&id, 10).iter().enumerate() {
+                lines.push((
+                    format!(
+                        "{:>2} {} {:>4} {:>3} {:>3} {}",
+                        idx + 1,
+                        format_time(run.frames),
+                        run.steps,
+                        run.deaths,
+                        run.undos,
+                        run.date
+                    ),
+                    *rank == Some(idx),
+                ));
+            }
+            if let Some(rank) = rank.filter(|rank| *rank >= 10) {
+                lines.push((format!("Your clear is #{}", rank + 1), true));
+            }
+            lines.push((String::new(), false));
+            lines.push(("Press Enter".to_string(), false));
+
+            // The banner is 160x38, on top of the text
+            let width = (8 * lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0) as i32
+                + 16)
+                .max(160 + 16);
+            let height = 12 * lines.len() as i32 + 12 + 38 + 4;
+            let x = (view_width as i32 - width) / 2 + x_offset;
+            let y = (view_height as i32 - height) / 2 + y_offset;
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+            canvas.fill_rect(Rect::new(x, y, width as u32, height as u32))?;
+            canvas.copy(
+                &assets.cleared_texture,
+                None,
+                Some(Rect::new(x + (width - 160) / 2, y + 8, 160, 38)),
+            )?;
+            let y = y + 38 + 4;
+            for (idx, (line, highlight)) in lines.iter().enumerate() {
+                let color = if *highlight {
+                    Color::RGB(255, 200, 0)
+                } else {
+                    Color::RGB(255, 255, 255)
+                };
+                canvas.string(
+                    (x + 8) as i16,
+                    (y + 8 + 12 * idx as i32) as i16,
+                    line,
+                    color,
+                )?;
+            }
+        }
+
+        // Ending of the pack, over the whole view
+        if let Some(Results { ending: true, .. }) = &results {
+            display.begin_frame(&mut canvas, VIEW_WIDTH, VIEW_HEIGHT)?;
+            // The picture is 240x320, scaled up to the height of the view
+            let width = VIEW_HEIGHT * 240 / 320;
+            canvas.copy(
+                &assets.end_texture,
+                None,
+                Some(Rect::new(
+                    (VIEW_WIDTH as i32 - width as i32) / 2,
+                    0,
+                    width,
+                    VIEW_HEIGHT,
+                )),
+            )?;
+            let mut lines = vec![format!("All {} levels cleared!", pack.name)];
+            let best = pack
+                .levels
+                .iter()
+                .map(|level| progress.best.get(&level.id()).map(|clear| clear.frames))
+                .sum::<Option<u32>>();
+            if let Some(frames) = best {
+                lines.push(format!("Sum of best times {}", format_time(frames)));
+            }
+            lines.push("Press Enter".to_string());
+            let width = 8 * lines.iter().map(|line| line.len()).max().unwrap_or(0) as i32 + 16;
+            let height = 12 * lines.len() as i32 + 12;
+            let x = (VIEW_WIDTH as i32 - width) / 2;
+            let y = VIEW_HEIGHT as i32 - height - 16;
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+            canvas.fill_rect(Rect::new(x, y, width as u32, height as u32))?;
+            for (idx, line) in lines.iter().enumerate() {
+                canvas.string(
+                    (x + 8) as i16,
+                    (y + 8 + 12 * idx as i32) as i16,
+                    line,
+                    Color::RGB(255, 255, 255),
+                )?;
+            }
+        }
+
+        if paused {
+            pause.draw(
+                &mut canvas,
+                &settings,
+                (view_width, view_height),
+                (x_offset, y_offset),
+            )?;
+        }
 
-        frame += 1;
-        sleep(Duration::from_millis(MS_PER_FRAME));
+        display.present(&mut canvas);
     }
 
+    if let Some(path) = &record_path {
+        save_recording(path, &recording)?;
+    }
     Ok(())
 }
 
+/// The size in points of the view of a level: the part around Bobby, or the
+/// whole map in full view.
+fn view_size(map: &Grid, full_view: bool) -> (u32, u32) {
+    if full_view {
+        (
+            (32 * map.width()).max(VIEW_WIDTH),
+            (32 * map.height()).max(VIEW_HEIGHT),
+        )
+    } else {
+        (VIEW_WIDTH, VIEW_HEIGHT)
+    }
+}
+
+/// The value of `--scale`, a whole number of screen units per point.
+fn parse_scale(value: Option<String>) -> Result<u32, Box<dyn std::error::Error>> {
+    let value = value.ok_or("Missing number after --scale")?;
+    match value.parse() {
+        Ok(0) => Err("Invalid --scale 0: must be at least 1".into()),
+        Ok(scale) => Ok(scale),
+        Err(err) => Err(format!("Invalid --scale {value}: {err}").into()),
+    }
+}
+
+/// A built-in level such as `normal-12`, or else a level file.
+fn find_level(arg: &str) -> Result<(LevelPack, usize), Box<dyn std::error::Error>> {
+    match arg.parse::<Map>() {
+        Ok(map) => LevelPack::containing(map),
+        Err(_) => Ok((LevelPack::single(&normalize(arg))?, 0)),
+    }
+}
+
+/// The absolute form of a path given on the command line: the ids of the
+/// levels and the pack saved stay the same when the game is started from
+/// another directory next time. A path that doesn't resolve is kept as it
+/// is, for loading it to report the error.
+fn normalize(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// The pack and index of a level by its id (see [`bobby_carrot::Level::id`]),
+/// in the pack of the manifest at `pack_path` if there is one.
+fn find_pack_level(
+    id: &str,
+    pack_path: Option<&Path>,
+) -> Result<(LevelPack, usize), Box<dyn std::error::Error>> {
+    let Some(pack_path) = pack_path else {
+        return find_level(id);
+    };
+    let pack = LevelPack::load(pack_path)?;
+    let level = pack
+        .levels
+        .iter()
+        .position(|level| level.id() == id)
+        .ok_or_else(|| format!("Not in the pack {}", pack_path.display()))?;
+    Ok((pack, level))
+}
+
+/// Keys bound to several actions only do the first one, which is likely a
+/// mistake in the key file.
+fn report_conflicts(bindings: &Bindings) {
+    for (code, actions) in bindings.conflicts() {
+        let names = actions
+            .iter()
+            .map(|action| action.name())
+            .collect::<Vec<_>>();
+        eprintln!("{} is bound to {}", code.name(), names.join(" and "));
+    }
+}
+
+/// Failing to save is reported but doesn't stop the game.
+fn save_data(
+    path: Option<&Path>,
+    save: impl FnOnce(&Path) -> Result<(), Box<dyn std::error::Error>>,
+) {
+    if let Some(path) = path {
+        if let Err(err) = save(path) {
+            eprintln!("Can't save: {err}");
+        }
+    }
+}
+
+/// `mm:ss` of a time counted in frames.
+fn format_time(frames: u32) -> String {
+    let secs = frames as u64 / FRAMES;
+    format!("{:02}:{:02}", (secs / 60).min(99), secs % 60)
+}
+
+/// Write the run of the level just left, unless nothing was played: the
+/// replay of a cleared level is not replaced by an empty one on quitting.
+fn save_recording(path: &Path, recording: &Replay) -> Result<(), Box<dyn std::error::Error>> {
+    if recording.inputs.is_empty() {
+        return Ok(());
+    }
+    recording.save(path)
+}
+
+/// `convert <input> <output>`: turns a `.blm` level into its text form, or a
+/// text level back into a `.blm`.
+fn convert(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let [input, output] = args else {
+        return Err("Usage: bobby-carrot convert <input> <output>".into());
+    };
+    let data = fs::read(input).map_err(|err| format!("{input}: {err}"))?;
+    let converted = if text::is_text(&data) {
+        text::text_to_blm(&String::from_utf8(data)?).map_err(|err| format!("{input}: {err}"))?
+    } else {
+        text::blm_to_text(&data)
+            .map_err(|err| format!("{input}: {err}"))?
+            .into_bytes()
+    };
+    fs::write(output, converted).map_err(|err| format!("{output}: {err}"))?;
+    Ok(())
+}
+
+/// `validate <files>`: reports the problems of each level, and exits with a
+/// non-zero code if there is any.
+fn validate(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        return Err("Usage: bobby-carrot validate <files>".into());
+    }
+    let mut failed = false;
+    for path in args {
+        match MapInfo::load(Path::new(path)) {
+            Ok(map_info) => {
+                for problem in bobby_carrot::validate(&map_info) {
+                    println!("{path}: {problem}");
+                    failed = true;
+                }
+            }
+            Err(err) => {
+                println!("{err}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// `solve <level> [--max-states <n>]`: prints the shortest moves finishing a
+/// built-in level (`normal-12`) or a level file, and exits with a non-zero
+/// code if there is none.
+fn solve(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: bobby-carrot solve <level> [--max-states <n>]";
+    let mut level = None;
+    let mut max_states = SOLVE_MAX_STATES;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-states" => {
+                let value = args.next().ok_or("Missing number after --max-states")?;
+                max_states = value
+                    .parse()
+                    .map_err(|err| format!("Invalid --max-states {value}: {err}"))?;
+            }
+            _ if level.is_none() => level = Some(arg.as_str()),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let level = level.ok_or(USAGE)?;
+    let (pack, index) = find_level(level)?;
+    let map_info = pack.levels[index].load_map_info()?;
+
+    match bobby_carrot::solve_with_limit(&GameState::new(map_info), max_states) {
+        Some(Solution::Solved(moves)) => {
+            println!("{level}: solved in {} moves", moves.len());
+            let mut runs: Vec<(Direction, usize)> = Vec::new();
+            for direction in moves {
+                match runs.last_mut() {
+                    Some((last, count)) if *last == direction => *count += 1,
+                    _ => runs.push((direction, 1)),
+                }
+            }
+            for (direction, count) in runs {
+                if count == 1 {
+                    println!("{direction:?}");
+                } else {
+                    println!("{direction:?} x{count}");
+                }
+            }
+        }
+        Some(Solution::Unsolvable { states }) => {
+            println!("{level}: unsolvable, all {states} reachable states explored");
+            process::exit(1);
+        }
+        None => {
+            println!("{level}: gave up after {max_states} states");
+            process::exit(2);
+        }
+    }
+    Ok(())
+}
+
+/// `generate <dir> [--seed <n>] [--count <n>] [--eggs] [--mechanics <list>]
+/// [--difficulty <n>]`: writes new 16x16 levels to the directory, each one
+/// finished by the solver first, with a `pack.txt` manifest to play them
+/// with `--pack`. It fails without writing the manifest if no seed gave a
+/// level.
+///
+/// The levels are named after their seed, the ones following `--seed` for
+/// `--count` levels. Without `--seed` they are different on every run. The
+/// mechanics are a comma separated list of `keys`, `rails`, `conveyors` and
+/// `crumble`, none by default.
+fn generate(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: bobby-carrot generate <dir> [--seed <n>] [--count <n>] [--eggs] \
+                         [--mechanics <list>] [--difficulty <n>]";
+    let mut dir = None;
+    let mut seed = None;
+    let mut count = 1;
+    let mut options = GenerateOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().ok_or("Missing number after --seed")?;
+                seed = Some(
+                    value
+                        .parse()
+                        .map_err(|err| format!("Invalid --seed {value}: {err}"))?,
+                );
+            }
+            "--count" => {
+                let value = args.next().ok_or("Missing number after --count")?;
+                count = value
+                    .parse()
+                    .map_err(|err| format!("Invalid --count {value}: {err}"))?;
+            }
+            "--eggs" => options.goal = Goal::Eggs,
+            "--mechanics" => {
+                let value = args.next().ok_or("Missing list after --mechanics")?;
+                options.mechanics = Mechanics::default();
+                for name in value.split(',').map(str::trim) {
+                    match name {
+                        "keys" => options.mechanics.keys = true,
+                        "rails" => options.mechanics.rails = true,
+                        "conveyors" => options.mechanics.conveyors = true,
+                        "crumble" => options.mechanics.crumble = true,
+                        _ => return Err(format!("Unknown mechanic: {name}").into()),
+                    }
+                }
+            }
+            "--difficulty" => {
+                let value = args.next().ok_or("Missing number after --difficulty")?;
+                options.difficulty = value
+                    .parse()
+                    .ok()
+                    .filter(|difficulty| (1..=MAX_DIFFICULTY).contains(difficulty))
+                    .ok_or_else(|| {
+                        format!("Invalid --difficulty {value}: expected 1 to {MAX_DIFFICULTY}")
+                    })?;
+            }
+            _ if dir.is_none() => dir = Some(Path::new(arg)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let dir = dir.ok_or(USAGE)?;
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs())
+    });
+
+    fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let goal = match options.goal {
+        Goal::Carrots => "carrots",
+        Goal::Eggs => "eggs",
+    };
+    let mut manifest = "name = Generated\n".to_string();
+    let mut levels = 0;
+    for seed in seed..seed.saturating_add(count) {
+        let Some(generated) = bobby_carrot::generate(seed, &options) else {
+            println!("{seed}: no solvable level found");
+            continue;
+        };
+        let name = format!("{goal}-{seed}.blm");
+        let path = dir.join(&name);
+        fs::write(&path, generated.map_info.to_blm())
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        println!(
+            "{}: solved in {} moves",
+            path.display(),
+            generated.moves.len()
+        );
+        manifest.push_str(&format!("level = {goal} {name}\n"));
+        levels += 1;
+    }
+    if levels == 0 {
+        return Err("No level generated, pack.txt isn't written".into());
+    }
+    key_value::write(&dir.join("pack.txt"), &manifest)?;
+    Ok(())
+}
+
+/// Solves the level in the background while hints are shown, a search is
+/// cancelled if the game changed in the meantime.
+#[derive(Default)]
+struct Hints {
+    enabled: bool,
+    generation: u64,
+    search: Option<Search>,
+    result: Option<(u64, Option<Solution>)>,
+}
+
+impl Hints {
+    /// The game changed, the current hint no longer applies.
+    fn invalidate(&mut self) {
+        self.generation += 1;
+    }
+
+    /// Collect a finished search or cancel an outdated one, and start a new
+    /// one if Bobby waits for a direction without an up to date hint.
+    fn update(&mut self, game: &GameState) {
+        if let Some(search) = &self.search {
+            if let Ok(solution) = search.receiver.try_recv() {
+                self.result = Some((search.generation, solution));
+                self.search = None;
+            } else if !self.enabled || search.generation != self.generation {
+                search.cancel.store(true, Ordering::Relaxed);
+                self.search = None;
+            }
+        }
+        let up_to_date =
+            matches!(self.result, Some((generation, _)) if generation == self.generation);
+        if !self.enabled || up_to_date || self.search.is_some() || !game.is_at_rest() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let game = game.clone();
+        let cancelled = Arc::clone(&cancel);
+        thread::spawn(move || {
+            let solution = bobby_carrot::solve_cancellable(&game, HINT_MAX_STATES, &cancelled);
+            let _ = sender.send(solution);
+        });
+        self.search = Some(Search {
+            generation: self.generation,
+            receiver,
+            cancel,
+        });
+    }
+
+    /// The search result for the game as it is now: `None` while searching,
+    /// `Some(None)` if the search gave up.
+    fn current(&self) -> Option<&Option<Solution>> {
+        match &self.result {
+            Some((generation, solution)) if self.enabled && *generation == self.generation => {
+                Some(solution)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A search of [`Hints`] running in the background.
+struct Search {
+    /// The generation of the game searched from.
+    generation: u64,
+    receiver: Receiver<Option<Solution>>,
+    /// Set to stop the search, its result is no longer wanted.
+    cancel: Arc<AtomicBool>,
+}
+
+/// The panel shown after the fade out of a cleared level, until the player
+/// goes back to the menu.
+struct Results {
+    run: Run,
+    /// Where the clear ranks on the leaderboard of the level, replays aren't
+    /// recorded.
+    rank: Option<usize>,
+    /// The last level of the pack was cleared and the ending is shown.
+    ending: bool,
+}
+
 struct Assets<'a> {
     bobby_idle_texture: Texture<'a>,
     bobby_death_texture: Texture<'a>,
@@ -453,6 +1160,15 @@ struct Assets<'a> {
     hud_texture: Texture<'a>,
     numbers_texture: Texture<'a>,
     help_texture: Texture<'a>,
+    arrow_left_texture: Texture<'a>,
+    arrow_right_texture: Texture<'a>,
+    arrow_up_texture: Texture<'a>,
+    arrow_down_texture: Texture<'a>,
+    title_texture: Texture<'a>,
+    cleared_texture: Texture<'a>,
+    end_texture: Texture<'a>,
+    /// Drawn on the title screen, if the picture can be loaded.
+    logo_texture: Option<Texture<'a>>,
 }
 
 impl<'a> Assets<'a> {
@@ -492,6 +1208,24 @@ impl<'a> Assets<'a> {
             texture_creator.load_texture_bytes(include_bytes!("assets/image/numbers.png"))?;
         let help_texture =
             texture_creator.load_texture_bytes(include_bytes!("assets/image/help.png"))?;
+        let arrow_left_texture =
+            texture_creator.load_texture_bytes(include_bytes!("assets/image/arrow_l.png"))?;
+        let arrow_right_texture =
+            texture_creator.load_texture_bytes(include_bytes!("assets/image/arrow_r.png"))?;
+        let arrow_up_texture =
+            texture_creator.load_texture_bytes(include_bytes!("assets/image/arrow_u.png"))?;
+        let arrow_down_texture =
+            texture_creator.load_texture_bytes(include_bytes!("assets/image/arrow_d.png"))?;
+        let title_texture =
+            texture_creator.load_texture_bytes(include_bytes!("assets/image/title.png"))?;
+        let cleared_texture =
+            texture_creator.load_texture_bytes(include_bytes!("assets/image/cleared.png"))?;
+        let end_texture =
+            texture_creator.load_texture_bytes(include_bytes!("assets/image/end.png"))?;
+        // logo.png is an empty file for now
+        let logo_texture = texture_creator
+            .load_texture_bytes(include_bytes!("assets/image/logo.png"))
+            .ok();
         Ok(Assets {
             bobby_idle_texture,
             bobby_death_texture,
@@ -509,549 +1243,375 @@ impl<'a> Assets<'a> {
             hud_texture,
             numbers_texture,
             help_texture,
+            arrow_left_texture,
+            arrow_right_texture,
+            arrow_up_texture,
+            arrow_down_texture,
+            title_texture,
+            cleared_texture,
+            end_texture,
+            logo_texture,
         })
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Map {
-    Normal(u32),
-    Egg(u32),
-}
-
-#[derive(Clone)]
-struct MapInfo {
-    data: Vec<u8>,
-    coord_start: (u32, u32),
-    carrot_total: usize,
-    egg_total: usize,
-}
-
-impl fmt::Display for Map {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Map::Normal(n) => write!(f, "Normal-{:02}", n),
-            Map::Egg(n) => write!(f, "Egg-{:02}", n),
+/// The tiles of a level, with the conveyors and the open finish animated.
+fn draw_tiles<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    assets: &Assets,
+    data: &Grid,
+    frame: u32,
+    finished: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for x in 0..data.width() {
+        for y in 0..data.height() {
+            let tile = data[(x, y)];
+            let texture = match tile {
+                Tile::Finish if finished => &assets.tile_finish_texture,
+                Tile::ConveyorLeft => &assets.tile_conveyor_left_texture,
+                Tile::ConveyorRight => &assets.tile_conveyor_right_texture,
+                Tile::ConveyorUp => &assets.tile_conveyor_up_texture,
+                Tile::ConveyorDown => &assets.tile_conveyor_down_texture,
+                _ => &assets.tileset_texture,
+            };
+            let src = if (tile == Tile::Finish && finished) || tile.conveyor().is_some() {
+                Rect::new(32 * ((frame as i32 / (FRAMES as i32 / 10)) % 4), 0, 32, 32)
+            } else {
+                tile_sprite(tile)
+            };
+            canvas.copy_ex(
+                texture,
+                Some(src),
+                Some(Rect::new(32 * x as i32, 32 * y as i32, 32, 32)),
+                0.0,
+                None,
+                false,
+                false,
+            )?;
         }
     }
+    Ok(())
 }
 
-impl Map {
-    fn load_map_info(&self) -> Result<MapInfo, Box<dyn std::error::Error>> {
-        let data = match self {
-            Map::Normal(1) => include_bytes!("assets/level/normal01.blm"),
-            Map::Normal(2) => include_bytes!("assets/level/normal02.blm"),
-            Map::Normal(3) => include_bytes!("assets/level/normal03.blm"),
-            Map::Normal(4) => include_bytes!("assets/level/normal04.blm"),
-            Map::Normal(5) => include_bytes!("assets/level/normal05.blm"),
-            Map::Normal(6) => include_bytes!("assets/level/normal06.blm"),
-            Map::Normal(7) => include_bytes!("assets/level/normal07.blm"),
-            Map::Normal(8) => include_bytes!("assets/level/normal08.blm"),
-            Map::Normal(9) => include_bytes!("assets/level/normal09.blm"),
-            Map::Normal(10) => include_bytes!("assets/level/normal10.blm"),
-            Map::Normal(11) => include_bytes!("assets/level/normal11.blm"),
-            Map::Normal(12) => include_bytes!("assets/level/normal12.blm"),
-            Map::Normal(13) => include_bytes!("assets/level/normal13.blm"),
-            Map::Normal(14) => include_bytes!("assets/level/normal14.blm"),
-            Map::Normal(15) => include_bytes!("assets/level/normal15.blm"),
-            Map::Normal(16) => include_bytes!("assets/level/normal16.blm"),
-            Map::Normal(17) => include_bytes!("assets/level/normal17.blm"),
-            Map::Normal(18) => include_bytes!("assets/level/normal18.blm"),
-            Map::Normal(19) => include_bytes!("assets/level/normal19.blm"),
-            Map::Normal(20) => include_bytes!("assets/level/normal20.blm"),
-            Map::Normal(21) => include_bytes!("assets/level/normal21.blm"),
-            Map::Normal(22) => include_bytes!("assets/level/normal22.blm"),
-            Map::Normal(23) => include_bytes!("assets/level/normal23.blm"),
-            Map::Normal(24) => include_bytes!("assets/level/normal24.blm"),
-            Map::Normal(25) => include_bytes!("assets/level/normal25.blm"),
-            Map::Normal(26) => include_bytes!("assets/level/normal26.blm"),
-            Map::Normal(27) => include_bytes!("assets/level/normal27.blm"),
-            Map::Normal(28) => include_bytes!("assets/level/normal28.blm"),
-            Map::Normal(29) => include_bytes!("assets/level/normal29.blm"),
-            Map::Normal(30) => include_bytes!("assets/level/normal30.blm"),
-            Map::Normal(level) => return Err(format!("Invalid normal level: {}", level).into()),
-            Map::Egg(1) => include_bytes!("assets/level/egg01.blm"),
-            Map::Egg(2) => include_bytes!("assets/level/egg02.blm"),
-            Map::Egg(3) => include_bytes!("assets/level/egg03.blm"),
-            Map::Egg(4) => include_bytes!("assets/level/egg04.blm"),
-            Map::Egg(5) => include_bytes!("assets/level/egg05.blm"),
-            Map::Egg(6) => include_bytes!("assets/level/egg06.blm"),
-            Map::Egg(7) => include_bytes!("assets/level/egg07.blm"),
-            Map::Egg(8) => include_bytes!("assets/level/egg08.blm"),
-            Map::Egg(9) => include_bytes!("assets/level/egg09.blm"),
-            Map::Egg(10) => include_bytes!("assets/level/egg10.blm"),
-            Map::Egg(11) => include_bytes!("assets/level/egg11.blm"),
-            Map::Egg(12) => include_bytes!("assets/level/egg12.blm"),
-            Map::Egg(13) => include_bytes!("assets/level/egg13.blm"),
-            Map::Egg(14) => include_bytes!("assets/level/egg14.blm"),
-            Map::Egg(15) => include_bytes!("assets/level/egg15.blm"),
-            Map::Egg(16) => include_bytes!("assets/level/egg16.blm"),
-            Map::Egg(17) => include_bytes!("assets/level/egg17.blm"),
-            Map::Egg(18) => include_bytes!("assets/level/egg18.blm"),
-            Map::Egg(19) => include_bytes!("assets/level/egg19.blm"),
-            Map::Egg(20) => include_bytes!("assets/level/egg20.blm"),
-            Map::Egg(level) => return Err(format!("Invalid egg level: {}", level).into()),
-        };
-        let data = data[4..].to_vec();
-        let mut start_idx: u32 = 0;
-        let mut carrot_total: usize = 0;
-        let mut egg_total: usize = 0;
-        for (idx, byte) in data.iter().enumerate() {
-            match byte {
-                19 => carrot_total += 1,
-                45 => egg_total += 1,
-                21 => start_idx = idx as u32,
-                _ => {}
-            }
-        }
-        Ok(MapInfo {
-            data,
-            coord_start: (start_idx % 16, start_idx / 16),
-            carrot_total,
-            egg_total,
-        })
-    }
+/// The carrots or eggs left, the keys, the time and the undos in the corners
+/// of `view`.
+fn draw_hud<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    assets: &Assets,
+    map_info: &MapInfo,
+    bobby: &Bobby,
+    frames: u32,
+    undos: usize,
+    view: Rect,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (x_offset, y_offset, x_right) = (view.x(), view.y(), view.right());
+    // Indicator
+    let (icon_width, num_left) = if map_info.goal == Goal::Carrots {
+        canvas.copy_ex(
+            &assets.hud_texture,
+            Some(Rect::new(0, 0, 46, 44)),
+            Some(Rect::new(x_right - (46 + 4), 4 + y_offset, 46, 44)),
+            0.0,
+            None,
+            false,
+            false,
+        )?;
+        (46, map_info.carrot_total - bobby.carrot_count)
+    } else {
+        canvas.copy_ex(
+            &assets.hud_texture,
+            Some(Rect::new(46, 0, 34, 44)),
+            Some(Rect::new(x_right - (34 + 4), 4 + y_offset, 34, 44)),
+            0.0,
+            None,
+            false,
+            false,
+        )?;
+        (34, map_info.egg_total - bobby.egg_count)
+    };
+    let num_10 = num_left as i32 / 10;
+    let num_01 = num_left as i32 % 10;
+    canvas.copy_ex(
+        &assets.numbers_texture,
+        Some(Rect::new(num_01 * 12, 0, 12, 18)),
+        Some(Rect::new(
+            x_right - (icon_width + 4) - 2 - 12,
+            4 + 14 + y_offset,
+            12,
+            18,
+        )),
+        0.0,
+        None,
+        false,
+        false,
+    )?;
+    canvas.copy_ex(
+        &assets.numbers_texture,
+        Some(Rect::new(num_10 * 12, 0, 12, 18)),
+        Some(Rect::new(
+            x_right - (icon_width + 4) - 2 - 12 * 2 - 1,
+            4 + 14 + y_offset,
+            12,
+            18,
+        )),
+        0.0,
+        None,
+        false,
+        false,
+    )?;
 
-    fn next(self) -> Map {
-        match self {
-            Map::Normal(n) if n < 30 => Map::Normal(n + 1),
-            Map::Normal(n) if n >= 30 => Map::Egg(1),
-            Map::Egg(n) if n < 20 => Map::Egg(n + 1),
-            Map::Egg(n) if n >= 20 => Map::Normal(1),
-            _ => Map::Normal(1),
-        }
+    // Key
+    let mut keys = Vec::new();
+    for _ in 0..bobby.key_gray {
+        keys.push((122, keys.len() as i32));
+    }
+    for _ in 0..bobby.key_yellow {
+        keys.push((122 + 22, keys.len() as i32));
+    }
+    for _ in 0..bobby.key_red {
+        keys.push((122 + 22 + 22, keys.len() as i32));
+    }
+    for (offset, count) in keys {
+        canvas.copy_ex(
+            &assets.hud_texture,
+            Some(Rect::new(offset, 0, 22, 44)),
+            Some(Rect::new(
+                x_right - (22 + 4) - count * 22,
+                4 + 44 + 2 + y_offset,
+                22,
+                44,
+            )),
+            0.0,
+            None,
+            false,
+            false,
+        )?;
     }
 
-    fn previous(self) -> Map {
-        match self {
-            Map::Normal(n) if n <= 1 => Map::Egg(20),
-            Map::Normal(n) if n > 1 => Map::Normal(n - 1),
-            Map::Egg(n) if n <= 1 => Map::Normal(30),
-            Map::Egg(n) if n > 1 => Map::Egg(n - 1),
-            _ => Map::Normal(1),
-        }
+    // time passed
+    let passed_secs = ((frames) as u64 / FRAMES) as i32;
+    let mut minutes = passed_secs / 60;
+    let mut seconds = passed_secs % 60;
+    if minutes > 99 {
+        minutes = 99;
+        seconds = 99;
+    }
+    for (idx, offset) in [minutes / 10, minutes % 10, 10, seconds / 10, seconds % 10]
+        .into_iter()
+        .enumerate()
+    {
+        canvas.copy_ex(
+            &assets.numbers_texture,
+            Some(Rect::new(offset * 12, 0, 12, 18)),
+            Some(Rect::new(
+                4 + 12 * idx as i32 + x_offset,
+                4 + y_offset,
+                12,
+                18,
+            )),
+            0.0,
+            None,
+            false,
+            false,
+        )?;
+    }
+    // the time doesn't compare with a run without undos
+    if undos > 0 {
+        let text = format!("{} undo", undos);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+        canvas.fill_rect(Rect::new(
+            4 + x_offset,
+            4 + 18 + 2 + y_offset,
+            8 * text.len() as u32 + 4,
+            12,
+        ))?;
+        canvas.string(
+            (4 + 2 + x_offset) as i16,
+            (4 + 18 + 4 + y_offset) as i16,
+            &text,
+            Color::RGB(255, 200, 0),
+        )?;
     }
+    Ok(())
 }
 
-#[derive(Debug)]
-struct Bobby {
-    state: State,
-    next_state: Option<State>,
-    start_frame: u32,
-    start_time: u32,
-    last_action_time: u32,
-    coord_src: (u32, u32),
-    coord_dest: (u32, u32),
-    // hud
-    carrot_count: usize,
-    egg_count: usize,
-    key_gray: usize,
-    key_yellow: usize,
-    key_red: usize,
-    faded_out: bool,
-    dead: bool,
+/// The still sprite of a tile in `tileset.png`.
+fn tile_sprite(tile: Tile) -> Rect {
+    let index = tile.sprite_index() as i32;
+    Rect::new(32 * (index % 8), 32 * (index / 8), 32, 32)
 }
 
-#[derive(Debug, Eq, PartialEq)]
-enum State {
-    Idle,
-    Death,
-    FadeIn,
-    FadeOut,
-    Left,
-    Right,
-    Up,
-    Down,
+fn draw_bobby<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    assets: &Assets,
+    bobby: &Bobby,
+    frame: u32,
+    previous: Option<Rect>,
+    alpha: f32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (src, dest) = bobby_rects(bobby, frame, previous, alpha);
+    let texture = match bobby.state {
+        State::Idle => &assets.bobby_idle_texture,
+        State::Death => &assets.bobby_death_texture,
+        State::FadeIn => &assets.bobby_fade_texture,
+        State::FadeOut => &assets.bobby_fade_texture,
+        State::Left => &assets.bobby_left_texture,
+        State::Right => &assets.bobby_right_texture,
+        State::Up => &assets.bobby_up_texture,
+        State::Down => &assets.bobby_down_texture,
+    };
+    canvas.copy_ex(texture, Some(src), Some(dest), 0.0, None, false, false)?;
+    Ok(())
 }
 
-impl Bobby {
-    pub fn new(start_frame: u32, start_time: u32, coord_src: (u32, u32)) -> Bobby {
-        Bobby {
-            state: State::FadeIn,
-            next_state: None,
-            start_frame,
-            start_time,
-            last_action_time: start_time,
-            coord_src,
-            coord_dest: coord_src,
-            // hud
-            carrot_count: 0,
-            egg_count: 0,
-            key_gray: 0,
-            key_yellow: 0,
-            key_red: 0,
-            faded_out: false,
-            dead: false,
-        }
-    }
-
-    fn update_texture_position(&mut self, frame: u32, map_data: &mut [u8]) -> (Rect, Rect) {
-        let delta_frame = frame - self.start_frame;
-        let is_walking = self.coord_src != self.coord_dest;
-        let step = delta_frame / FRAMES_PER_STEP;
-        // println!("frame: {frame}, step: {step}, bobby: {:?}", self);
-        let (src, dest) = match self.state {
-            State::Idle => {
-                let step_idle = (step / 2) % 3;
-                let src = Rect::new(36 * step_idle as i32, 0, 36, 50);
-                let dest = Rect::new(
-                    self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                    self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    36,
-                    50,
-                );
-                return (src, dest);
-            }
-            State::Death => {
-                let mut step_death = step / 3;
-                if step_death > 7 {
-                    step_death = 7;
-                }
-                let src = Rect::new((step_death % 8) as i32 * 44, 0, 44, 54);
-                let x0 = self.coord_src.0 as i32 * 32;
-                let y0 = self.coord_src.1 as i32 * 32;
-                let x1 = self.coord_dest.0 as i32 * 32;
-                let y1 = self.coord_dest.1 as i32 * 32;
-                let x = (x1 - x0) / 2 + x0;
-                let y = (y1 - y0) / 2 + y0;
-                let dest = Rect::new(x + 16 - (44 / 2), y + 16 - (54 - 32 / 2), 44, 54);
-                if step / 3 >= 12 {
-                    self.dead = true;
-                }
-                return (src, dest);
-            }
-            State::FadeIn => {
-                let src = Rect::new((8 - step as i32) * 36, 0, 36, 50);
-                let dest = Rect::new(
-                    self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                    self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    36,
-                    50,
-                );
-                if step >= 8 {
-                    self.start_frame = frame;
-                    self.state = State::Down;
-                }
-                return (src, dest);
-            }
-            State::FadeOut => {
-                let src = Rect::new(step as i32 * 36, 0, 36, 50);
-                let dest = Rect::new(
-                    self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                    self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    36,
-                    50,
-                );
-                if step >= 8 {
-                    self.faded_out = true;
-                }
-                return (src, dest);
-            }
-            State::Left => {
-                let (src_x, dest_x, dest_y) = if is_walking {
-                    (
-                        36 * ((step + 7) % 8) as i32,
-                        (self.coord_src.0 as i32 * 8 - step as i32) * 32 / 8 + 16 - (36 / 2),
-                        self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    )
-                } else {
-                    (
-                        36 * 7,
-                        self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                        self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    )
-                };
-                let src = Rect::new(src_x, 0, 36, 50);
-                let dest = Rect::new(dest_x, dest_y, 36, 50);
-                if is_walking {
-                    assert_eq!(self.coord_src.0, self.coord_dest.0 + 1);
-                    assert_eq!(self.coord_src.1, self.coord_dest.1);
-                }
-                (src, dest)
-            }
-            State::Right => {
-                let (src_x, dest_x, dest_y) = if is_walking {
-                    (
-                        36 * ((step + 7) % 8) as i32,
-                        (self.coord_src.0 as i32 * 8 + step as i32) * 32 / 8 + 16 - (36 / 2),
-                        self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    )
-                } else {
-                    (
-                        36 * 7,
-                        self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                        self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    )
-                };
-                let src = Rect::new(src_x, 0, 36, 50);
-                let dest = Rect::new(dest_x, dest_y, 36, 50);
-                if is_walking {
-                    assert_eq!(self.coord_src.0 + 1, self.coord_dest.0);
-                    assert_eq!(self.coord_src.1, self.coord_dest.1);
-                }
-                (src, dest)
-            }
-            State::Up => {
-                let (src_x, dest_x, dest_y) = if is_walking {
-                    (
-                        36 * ((step + 7) % 8) as i32,
-                        self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                        (self.coord_src.1 as i32 * 8 - step as i32) * 32 / 8 + 16 - (50 - 32 / 2),
-                    )
-                } else {
-                    (
-                        36 * 7,
-                        self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                        self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    )
-                };
-                let src = Rect::new(src_x, 0, 36, 50);
-                let dest = Rect::new(dest_x, dest_y, 36, 50);
-                if is_walking {
-                    assert_eq!(self.coord_src.0, self.coord_dest.0);
-                    assert_eq!(self.coord_src.1, self.coord_dest.1 + 1);
-                }
-                (src, dest)
-            }
-            State::Down => {
-                let (src_x, dest_x, dest_y) = if is_walking {
-                    (
-                        36 * ((step + 7) % 8) as i32,
-                        self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                        (self.coord_src.1 as i32 * 8 + step as i32) * 32 / 8 + 16 - (50 - 32 / 2),
-                    )
-                } else {
-                    (
-                        36 * 7,
-                        self.coord_src.0 as i32 * 32 + 16 - (36 / 2),
-                        self.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
-                    )
-                };
-                let src = Rect::new(src_x, 0, 36, 50);
-                let dest = Rect::new(dest_x, dest_y, 36, 50);
-                if is_walking {
-                    assert_eq!(self.coord_src.0, self.coord_dest.0);
-                    assert_eq!(self.coord_src.1 + 1, self.coord_dest.1);
-                }
-                (src, dest)
-            }
-        };
-
-        if step == 6 && is_walking && self.next_state == Some(State::Death) {
-            self.start_frame = frame;
-            self.state = State::Death;
-        } else if step == 8 && is_walking {
-            let old_pos = (self.coord_src.0 + self.coord_src.1 * 16) as usize;
-            let new_pos = (self.coord_dest.0 + self.coord_dest.1 * 16) as usize;
-            match map_data[old_pos] {
-                24 => map_data[old_pos] = 25,
-                25 => map_data[old_pos] = 26,
-                26 => map_data[old_pos] = 27,
-                27 => map_data[old_pos] = 24,
-                28 => map_data[old_pos] = 29,
-                29 => map_data[old_pos] = 28,
-                30 => map_data[old_pos] = 31,
-                45 => {
-                    map_data[old_pos] = 46;
-                    self.egg_count += 1;
-                }
-                _ => {
-                    // TODO
-                }
-            }
-            match map_data[new_pos] {
-                // get carrot
-                19 => {
-                    map_data[new_pos] = 20;
-                    self.carrot_count += 1;
-                }
-                // red switch
-                22 => {
-                    for x in 0..WIDTH_POINTS {
-                        for y in 0..HEIGHT_POINTS {
-                            let pos = x as usize + y as usize * 16;
-                            match map_data[pos] {
-                                // switch
-                                22 => map_data[pos] = 23,
-                                23 => map_data[pos] = 22,
-                                // right angle
-                                24 => map_data[pos] = 25,
-                                25 => map_data[pos] = 26,
-                                26 => map_data[pos] = 27,
-                                27 => map_data[pos] = 24,
-                                // line
-                                28 => map_data[pos] = 29,
-                                29 => map_data[pos] = 28,
-                                _ => {}
-                            }
-                        }
-                    }
-                }
-                // TODO: dead
-                31 => {}
-                // gray lock
-                32 => {
-                    map_data[new_pos] = 18;
-                    self.key_gray += 1;
-                }
-                33 if self.key_gray > 0 => {
-                    map_data[new_pos] = 18;
-                    self.key_gray -= 1;
-                }
-                // yellow lock
-                34 => {
-                    map_data[new_pos] = 18;
-                    self.key_yellow += 1;
-                }
-                35 if self.key_yellow > 0 => {
-                    map_data[new_pos] = 18;
-                    self.key_yellow -= 1;
-                }
-                // red lock
-                36 => {
-                    map_data[new_pos] = 18;
-                    self.key_red += 1;
-                }
-                37 if self.key_red > 0 => {
-                    map_data[new_pos] = 18;
-                    self.key_red -= 1;
-                }
-                // yellow switch
-                38 => {
-                    for x in 0..WIDTH_POINTS {
-                        for y in 0..HEIGHT_POINTS {
-                            let pos = x as usize + y as usize * 16;
-                            match map_data[pos] {
-                                // switch
-                                38 => map_data[pos] = 39,
-                                39 => map_data[pos] = 38,
-                                // left / right
-                                40 => map_data[pos] = 41,
-                                41 => map_data[pos] = 40,
-                                // up / down
-                                42 => map_data[pos] = 43,
-                                43 => map_data[pos] = 42,
-                                _ => {}
-                            }
-                        }
-                    }
-                }
-                // flow
-                40 => self.next_state = Some(State::Left),
-                41 => self.next_state = Some(State::Right),
-                42 => self.next_state = Some(State::Up),
-                43 => self.next_state = Some(State::Down),
-                _ => {}
-            }
+/// Source rect in the sprite sheet of the current state, and destination rect
+/// on the map, `alpha` of the way from the `previous` destination to the one
+/// of the current frame.
+fn bobby_rects(bobby: &Bobby, frame: u32, previous: Option<Rect>, alpha: f32) -> (Rect, Rect) {
+    let (src, dest) = bobby_frame_rects(bobby, frame);
+    let Some(previous) = previous else {
+        return (src, dest);
+    };
+    let lerp = |from: i32, to: i32| from + ((to - from) as f32 * alpha.min(1.0)).round() as i32;
+    let dest = Rect::new(
+        lerp(previous.x(), dest.x()),
+        lerp(previous.y(), dest.y()),
+        dest.width(),
+        dest.height(),
+    );
+    (src, dest)
+}
 
-            self.coord_src = self.coord_dest;
-            self.start_frame = frame;
-            if let Some(state) = self.next_state.take() {
-                self.update_state(state, frame, map_data);
+/// Source rect in the sprite sheet of the current state, and destination rect
+/// on the map, at `frame`.
+fn bobby_frame_rects(bobby: &Bobby, frame: u32) -> (Rect, Rect) {
+    let delta_frame = frame - bobby.start_frame;
+    let is_walking = bobby.is_walking();
+    let step = delta_frame / FRAMES_PER_STEP;
+    // Pixels walked since leaving the tile
+    let walked = (delta_frame * 32 / (8 * FRAMES_PER_STEP)).min(32) as i32;
+    match bobby.state {
+        State::Idle => {
+            let step_idle = (step / 2) % 3;
+            let src = Rect::new(36 * step_idle as i32, 0, 36, 50);
+            let dest = Rect::new(
+                bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                36,
+                50,
+            );
+            (src, dest)
+        }
+        State::Death => {
+            let mut step_death = step / 3;
+            if step_death > 7 {
+                step_death = 7;
             }
+            let src = Rect::new((step_death % 8) as i32 * 44, 0, 44, 54);
+            let x0 = bobby.coord_src.0 as i32 * 32;
+            let y0 = bobby.coord_src.1 as i32 * 32;
+            let x1 = bobby.coord_dest.0 as i32 * 32;
+            let y1 = bobby.coord_dest.1 as i32 * 32;
+            let x = (x1 - x0) / 2 + x0;
+            let y = (y1 - y0) / 2 + y0;
+            let dest = Rect::new(x + 16 - (44 / 2), y + 16 - (54 - 32 / 2), 44, 54);
+            (src, dest)
         }
-        (src, dest)
-    }
-
-    fn is_walking(&self) -> bool {
-        self.coord_src != self.coord_dest
-    }
-
-    fn is_finished(&self, map_info: &MapInfo) -> bool {
-        if map_info.carrot_total > 0 {
-            self.carrot_count == map_info.carrot_total
-        } else {
-            self.egg_count == map_info.egg_total
+        State::FadeIn => {
+            let src = Rect::new((8 - step.min(8) as i32) * 36, 0, 36, 50);
+            let dest = Rect::new(
+                bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                36,
+                50,
+            );
+            (src, dest)
         }
-    }
-
-    fn update_next_state(&mut self, state: State, frame: u32) {
-        if (frame - self.start_frame) / FRAMES_PER_STEP > 3
-            && self.next_state != Some(State::Idle)
-            && self.next_state != Some(State::Death)
-            && self.next_state != Some(State::FadeIn)
-            && self.next_state != Some(State::FadeOut)
-        {
-            self.next_state = Some(state);
+        State::FadeOut => {
+            let src = Rect::new(step.min(8) as i32 * 36, 0, 36, 50);
+            let dest = Rect::new(
+                bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                36,
+                50,
+            );
+            (src, dest)
         }
-    }
-
-    fn update_state(&mut self, state: State, frame: u32, map_data: &[u8]) {
-        // println!("new state: {:?}", state);
-        self.start_frame = frame;
-        self.state = state;
-        self.update_dest(map_data);
-    }
-
-    fn update_dest(&mut self, map_data: &[u8]) {
-        let old_dest = self.coord_dest;
-        match self.state {
-            State::Left => {
-                if self.coord_dest.0 > 0 {
-                    self.coord_dest.0 -= 1;
-                }
-            }
-            State::Right => {
-                if self.coord_dest.0 < WIDTH_POINTS - 1 {
-                    self.coord_dest.0 += 1;
-                }
-            }
-            State::Up => {
-                if self.coord_dest.1 > 0 {
-                    self.coord_dest.1 -= 1;
-                }
-            }
-            State::Down => {
-                if self.coord_dest.1 < HEIGHT_POINTS - 1 {
-                    self.coord_dest.1 += 1;
-                }
-            }
-            _ => {}
+        State::Left => {
+            let (src_x, dest_x, dest_y) = if is_walking {
+                (
+                    36 * ((step + 7) % 8) as i32,
+                    bobby.coord_src.0 as i32 * 32 - walked + 16 - (36 / 2),
+                    bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                )
+            } else {
+                (
+                    36 * 7,
+                    bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                    bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                )
+            };
+            (
+                Rect::new(src_x, 0, 36, 50),
+                Rect::new(dest_x, dest_y, 36, 50),
+            )
         }
-
-        let old_pos = (self.coord_src.0 + self.coord_src.1 * 16) as usize;
-        let new_pos = (self.coord_dest.0 + self.coord_dest.1 * 16) as usize;
-        let old_item = map_data[old_pos];
-        let new_item = map_data[new_pos];
-        // The target position is forbidden
-        if new_item < 18
-            // lock
-            || (new_item == 33 && self.key_gray == 0)
-            || (new_item == 35 && self.key_yellow == 0)
-            || (new_item == 37 && self.key_red == 0)
-            // stop by sibling item
-            || (new_item == 24 && (self.state == State::Right || self.state == State::Down))
-            || (new_item == 25 && (self.state == State::Left || self.state == State::Down))
-            || (new_item == 26 && (self.state == State::Left || self.state == State::Up))
-            || (new_item == 27 && (self.state == State::Right || self.state == State::Up))
-            || ((new_item == 28 || new_item == 40 || new_item == 41)
-                && (self.state == State::Up || self.state == State::Down))
-            || ((new_item == 29 || new_item == 42 || new_item == 43)
-                && (self.state == State::Left || self.state == State::Right))
-            // stop by flow
-            || (new_item == 40 && self.state == State::Right)
-            || (new_item == 41 && self.state == State::Left)
-            || (new_item == 42 && self.state == State::Down)
-            || (new_item == 43 && self.state == State::Up)
-            // egg
-            || (new_item == 46)
-            // stop by current item
-            || (old_item == 24 && (self.state == State::Left || self.state == State::Up))
-            || (old_item == 25 && (self.state == State::Right || self.state == State::Up))
-            || (old_item == 26 && (self.state == State::Right || self.state == State::Down))
-            || (old_item == 27 && (self.state == State::Left || self.state == State::Down))
-            || ((old_item == 28 || old_item == 40 || old_item == 41)
-                && (self.state == State::Up || self.state == State::Down))
-            || ((old_item == 29 || old_item == 42 || old_item == 43)
-                && (self.state == State::Left || self.state == State::Right))
-            || (old_item == 40 && self.state == State::Right)
-            || (old_item == 41 && self.state == State::Left)
-            || (old_item == 42 && self.state == State::Down)
-            || (old_item == 43 && self.state == State::Up)
-        {
-            self.coord_dest = old_dest;
-        } else if new_item == 31 {
-            self.next_state = Some(State::Death);
+        State::Right => {
+            let (src_x, dest_x, dest_y) = if is_walking {
+                (
+                    36 * ((step + 7) % 8) as i32,
+                    bobby.coord_src.0 as i32 * 32 + walked + 16 - (36 / 2),
+                    bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                )
+            } else {
+                (
+                    36 * 7,
+                    bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                    bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                )
+            };
+            (
+                Rect::new(src_x, 0, 36, 50),
+                Rect::new(dest_x, dest_y, 36, 50),
+            )
+        }
+        State::Up => {
+            let (src_x, dest_x, dest_y) = if is_walking {
+                (
+                    36 * ((step + 7) % 8) as i32,
+                    bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                    bobby.coord_src.1 as i32 * 32 - walked + 16 - (50 - 32 / 2),
+                )
+            } else {
+                (
+                    36 * 7,
+                    bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                    bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                )
+            };
+            (
+                Rect::new(src_x, 0, 36, 50),
+                Rect::new(dest_x, dest_y, 36, 50),
+            )
+        }
+        State::Down => {
+            let (src_x, dest_x, dest_y) = if is_walking {
+                (
+                    36 * ((step + 7) % 8) as i32,
+                    bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                    bobby.coord_src.1 as i32 * 32 + walked + 16 - (50 - 32 / 2),
+                )
+            } else {
+                (
+                    36 * 7,
+                    bobby.coord_src.0 as i32 * 32 + 16 - (36 / 2),
+                    bobby.coord_src.1 as i32 * 32 + 16 - (50 - 32 / 2),
+                )
+            };
+            (
+                Rect::new(src_x, 0, 36, 50),
+                Rect::new(dest_x, dest_y, 36, 50),
+            )
         }
     }
 }
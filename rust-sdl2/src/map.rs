@@ -0,0 +1,229 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::grid::Grid;
+use crate::text;
+use crate::tile::Tile;
+use crate::{HEIGHT_POINTS, WIDTH_POINTS};
+
+/// Every original `.blm` file starts with these 4 bytes, the third one is the
+/// version of the format. Version 1 levels are 16x16 tiles.
+pub const BLM_HEADER: [u8; 4] = [0, 0, 1, 0];
+/// From this version on, the width and the height of the level follow the
+/// header, one byte each.
+pub const BLM_VERSION: u8 = 2;
+
+/// Number of built-in normal levels.
+pub const NORMAL_LEVELS: u32 = 30;
+/// Number of built-in egg levels.
+pub const EGG_LEVELS: u32 = 20;
+
+/// One of the levels compiled into the binary.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Map {
+    Normal(u32),
+    Egg(u32),
+}
+
+/// What has to be collected to open the finish tile.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Goal {
+    Carrots,
+    Eggs,
+}
+
+#[derive(Clone)]
+pub struct MapInfo {
+    pub data: Grid,
+    pub coord_start: (u32, u32),
+    pub carrot_total: usize,
+    pub egg_total: usize,
+    pub goal: Goal,
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Map::Normal(n) => write!(f, "Normal-{:02}", n),
+            Map::Egg(n) => write!(f, "Egg-{:02}", n),
+        }
+    }
+}
+
+/// Parses the built-in levels, such as `normal-12` or `Egg-03`.
+impl FromStr for Map {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Map, Self::Err> {
+        let (type_str, num_str) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid map: {s}"))?;
+        let num: u32 = num_str.parse()?;
+        match type_str.to_ascii_lowercase().as_str() {
+            "normal" => Ok(Map::Normal(num)),
+            "egg" => Ok(Map::Egg(num)),
+            _ => Err(format!("Invalid map: {s}").into()),
+        }
+    }
+}
+
+impl Map {
+    pub fn load_map_info(&self) -> Result<MapInfo, Box<dyn std::error::Error>> {
+        let data: &[u8] = match self {
+            Map::Normal(1) => include_bytes!("assets/level/normal01.blm"),
+            Map::Normal(2) => include_bytes!("assets/level/normal02.blm"),
+            Map::Normal(3) => include_bytes!("assets/level/normal03.blm"),
+            Map::Normal(4) => include_bytes!("assets/level/normal04.blm"),
+            Map::Normal(5) => include_bytes!("assets/level/normal05.blm"),
+            Map::Normal(6) => include_bytes!("assets/level/normal06.blm"),
+            Map::Normal(7) => include_bytes!("assets/level/normal07.blm"),
+            Map::Normal(8) => include_bytes!("assets/level/normal08.blm"),
+            Map::Normal(9) => include_bytes!("assets/level/normal09.blm"),
+            Map::Normal(10) => include_bytes!("assets/level/normal10.blm"),
+            Map::Normal(11) => include_bytes!("assets/level/normal11.blm"),
+            Map::Normal(12) => include_bytes!("assets/level/normal12.blm"),
+            Map::Normal(13) => include_bytes!("assets/level/normal13.blm"),
+            Map::Normal(14) => include_bytes!("assets/level/normal14.blm"),
+            Map::Normal(15) => include_bytes!("assets/level/normal15.blm"),
+            Map::Normal(16) => include_bytes!("assets/level/normal16.blm"),
+            Map::Normal(17) => include_bytes!("assets/level/normal17.blm"),
+            Map::Normal(18) => include_bytes!("assets/level/normal18.blm"),
+            Map::Normal(19) => include_bytes!("assets/level/normal19.blm"),
+            Map::Normal(20) => include_bytes!("assets/level/normal20.blm"),
+            Map::Normal(21) => include_bytes!("assets/level/normal21.blm"),
+            Map::Normal(22) => include_bytes!("assets/level/normal22.blm"),
+            Map::Normal(23) => include_bytes!("assets/level/normal23.blm"),
+            Map::Normal(24) => include_bytes!("assets/level/normal24.blm"),
+            Map::Normal(25) => include_bytes!("assets/level/normal25.blm"),
+            Map::Normal(26) => include_bytes!("assets/level/normal26.blm"),
+            Map::Normal(27) => include_bytes!("assets/level/normal27.blm"),
+            Map::Normal(28) => include_bytes!("assets/level/normal28.blm"),
+            Map::Normal(29) => include_bytes!("assets/level/normal29.blm"),
+            Map::Normal(30) => include_bytes!("assets/level/normal30.blm"),
+            Map::Normal(level) => return Err(format!("Invalid normal level: {}", level).into()),
+            Map::Egg(1) => include_bytes!("assets/level/egg01.blm"),
+            Map::Egg(2) => include_bytes!("assets/level/egg02.blm"),
+            Map::Egg(3) => include_bytes!("assets/level/egg03.blm"),
+            Map::Egg(4) => include_bytes!("assets/level/egg04.blm"),
+            Map::Egg(5) => include_bytes!("assets/level/egg05.blm"),
+            Map::Egg(6) => include_bytes!("assets/level/egg06.blm"),
+            Map::Egg(7) => include_bytes!("assets/level/egg07.blm"),
+            Map::Egg(8) => include_bytes!("assets/level/egg08.blm"),
+            Map::Egg(9) => include_bytes!("assets/level/egg09.blm"),
+            Map::Egg(10) => include_bytes!("assets/level/egg10.blm"),
+            Map::Egg(11) => include_bytes!("assets/level/egg11.blm"),
+            Map::Egg(12) => include_bytes!("assets/level/egg12.blm"),
+            Map::Egg(13) => include_bytes!("assets/level/egg13.blm"),
+            Map::Egg(14) => include_bytes!("assets/level/egg14.blm"),
+            Map::Egg(15) => include_bytes!("assets/level/egg15.blm"),
+            Map::Egg(16) => include_bytes!("assets/level/egg16.blm"),
+            Map::Egg(17) => include_bytes!("assets/level/egg17.blm"),
+            Map::Egg(18) => include_bytes!("assets/level/egg18.blm"),
+            Map::Egg(19) => include_bytes!("assets/level/egg19.blm"),
+            Map::Egg(20) => include_bytes!("assets/level/egg20.blm"),
+            Map::Egg(level) => return Err(format!("Invalid egg level: {}", level).into()),
+        };
+        MapInfo::from_blm(data).map_err(|err| format!("{}: {}", self, err).into())
+    }
+}
+
+impl MapInfo {
+    /// Read a level file from disk, either a `.blm` or its text form.
+    pub fn load(path: &Path) -> Result<MapInfo, Box<dyn std::error::Error>> {
+        let data = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let data = if text::is_text(&data) {
+            let text = String::from_utf8(data)?;
+            text::text_to_blm(&text).map_err(|err| format!("{}: {}", path.display(), err))?
+        } else {
+            data
+        };
+        MapInfo::from_blm(&data).map_err(|err| format!("{}: {}", path.display(), err).into())
+    }
+
+    /// Parse the content of a `.blm` file: a header, see [`blm_size`],
+    /// followed by one byte per tile, row by row.
+    pub fn from_blm(data: &[u8]) -> Result<MapInfo, Box<dyn std::error::Error>> {
+        let (width, height, header_len) = blm_size(data)?;
+        let expected = header_len + (width * height) as usize;
+        if data.len() != expected {
+            return Err(format!(
+                "Invalid level size: expected {} bytes, got {}",
+                expected,
+                data.len()
+            )
+            .into());
+        }
+        let tiles = data[header_len..]
+            .iter()
+            .enumerate()
+            .map(|(idx, byte)| {
+                Tile::from_byte(*byte).ok_or_else(|| {
+                    let (x, y) = (idx as u32 % width, idx as u32 / width);
+                    format!("Invalid tile {} at ({}, {})", byte, x, y)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MapInfo::from_tiles(Grid::from_tiles(width, height, tiles)))
+    }
+
+    /// A level made of `data`. The goal is guessed from its content.
+    pub fn from_tiles(data: Grid) -> MapInfo {
+        let mut start_idx = 0;
+        let mut carrot_total: usize = 0;
+        let mut egg_total: usize = 0;
+        for (idx, tile) in data.tiles().iter().enumerate() {
+            match tile {
+                Tile::Carrot => carrot_total += 1,
+                Tile::Egg => egg_total += 1,
+                Tile::Start => start_idx = idx,
+                _ => {}
+            }
+        }
+        MapInfo {
+            coord_start: data.coord(start_idx),
+            data,
+            carrot_total,
+            egg_total,
+            goal: if carrot_total > 0 {
+                Goal::Carrots
+            } else {
+                Goal::Eggs
+            },
+        }
+    }
+
+    /// The content of the `.blm` file of this level, in version 1 if it has
+    /// the size of the original levels.
+    pub fn to_blm(&self) -> Vec<u8> {
+        let (width, height) = (self.data.width(), self.data.height());
+        let mut data = BLM_HEADER.to_vec();
+        if (width, height) != (WIDTH_POINTS, HEIGHT_POINTS) {
+            data[2] = BLM_VERSION;
+            data.extend([width as u8, height as u8]);
+        }
+        data.extend(self.data.tiles().iter().map(|tile| tile.to_byte()));
+        data
+    }
+}
+
+/// The width and the height in tiles of a `.blm` level, and the length of
+/// its header, read from the start of `data`.
+pub(crate) fn blm_size(data: &[u8]) -> Result<(u32, u32, usize), Box<dyn std::error::Error>> {
+    let header = data.get(..BLM_HEADER.len()).unwrap_or(data);
+    match *header {
+        [0, 0, 1, 0] => Ok((WIDTH_POINTS, HEIGHT_POINTS, BLM_HEADER.len())),
+        [0, 0, BLM_VERSION, 0] => {
+            let Some(&[width, height]) = data.get(BLM_HEADER.len()..BLM_HEADER.len() + 2) else {
+                return Err("Missing level size after the header".into());
+            };
+            if width == 0 || height == 0 {
+                return Err(format!("Invalid level size: {width}x{height}").into());
+            }
+            Ok((width as u32, height as u32, BLM_HEADER.len() + 2))
+        }
+        [0, 0, version, 0] => Err(format!("Unsupported level version: {version}").into()),
+        _ => Err(format!("Invalid level header: {:?}", header).into()),
+    }
+}
@@ -0,0 +1,576 @@
+use std::path::PathBuf;
+
+use bobby_carrot::{LevelPack, LevelSource, Progress};
+use sdl2::{
+    event::Event,
+    gfx::primitives::DrawRenderer,
+    keyboard::Keycode,
+    mouse::MouseButton,
+    pixels::Color,
+    rect::Rect,
+    render::{Texture, WindowCanvas},
+};
+
+use crate::bindings::{Action, Bindings};
+use crate::{format_time, VIEW_HEIGHT, VIEW_WIDTH};
+
+const COLUMNS: usize = 5;
+const ROWS: usize = 6;
+const CELL_WIDTH: i32 = 56;
+const CELL_HEIGHT: i32 = 40;
+const CELL_GAP: i32 = 4;
+const GRID_X: i32 = (VIEW_WIDTH as i32 - COLUMNS as i32 * (CELL_WIDTH + CELL_GAP) + CELL_GAP) / 2;
+const GRID_Y: i32 = 36;
+const TAB_HEIGHT: i32 = 16;
+
+/// What the menu asks of the game.
+pub enum Choice {
+    /// Play a level of a pack, with the path of its manifest if it has one.
+    Play(LevelPack, Option<PathBuf>, usize),
+    /// The key bindings were changed and are to be saved.
+    KeysChanged,
+    /// Back to the paused game the level grid was shown from.
+    Resume,
+    Fullscreen,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Page {
+    Title,
+    Levels,
+    /// The keys of each action, to rebind them.
+    Keys,
+}
+
+/// The title screen, then a grid of the levels of each pack.
+///
+/// A level is unlocked once the one before it in its pack is cleared.
+pub struct Menu {
+    /// The built-in packs, then the pack given on the command line.
+    packs: Vec<(LevelPack, Option<PathBuf>)>,
+    page: Page,
+    tab: usize,
+    selected: usize,
+    /// The page to go back to from the keys.
+    keys_back: Page,
+    /// The action selected on the keys page, in [`Action::ALL`].
+    action: usize,
+    /// The next key pressed is added to the selected action.
+    waiting_key: bool,
+    /// The level grid was shown from the paused game, leaving it goes back
+    /// there rather than to the title screen.
+    from_game: bool,
+}
+
+impl Menu {
+    /// Opens on the title screen, with `level` of `pack` selected.
+    pub fn new(pack: &LevelPack, pack_path: Option<PathBuf>, level: usize) -> Menu {
+        let mut packs = vec![(LevelPack::normal(), None), (LevelPack::egg(), None)];
+        let is_builtin = pack
+            .levels
+            .iter()
+            .all(|level| matches!(level.source, LevelSource::Builtin(_)));
+        if !is_builtin {
+            packs.push((pack.clone(), pack_path));
+        }
+        let mut menu = Menu {
+            packs,
+            page: Page::Title,
+            tab: 0,
+            selected: 0,
+            keys_back: Page::Title,
+            action: 0,
+            waiting_key: false,
+            from_game: false,
+        };
+        menu.select(&pack.levels[level].id());
+        menu
+    }
+
+    /// Go to the level grid, with the level of the given id selected.
+    pub fn show_levels(&mut self, id: &str) {
+        self.page = Page::Levels;
+        self.from_game = false;
+        self.select(id);
+    }
+
+    /// Like [`Menu::show_levels`], from the pause menu: leaving the grid
+    /// goes back to the paused game.
+    pub fn show_levels_from_pause(&mut self, id: &str) {
+        self.show_levels(id);
+        self.from_game = true;
+    }
+
+    /// Leave the level grid.
+    fn back(&mut self) -> Option<Choice> {
+        if self.from_game {
+            self.from_game = false;
+            return Some(Choice::Resume);
+        }
+        self.page = Page::Title;
+        None
+    }
+
+    fn select(&mut self, id: &str) {
+        for (tab, (pack, _)) in self.packs.iter().enumerate() {
+            if let Some(level) = pack.levels.iter().position(|level| level.id() == id) {
+                self.tab = tab;
+                self.selected = level;
+            }
+        }
+    }
+
+    fn is_unlocked(&self, progress: &Progress, level: usize) -> bool {
+        let levels = &self.packs[self.tab].0.levels;
+        level == 0
+            || progress.is_cleared(&levels[level].id())
+            || progress.is_cleared(&levels[level - 1].id())
+    }
+
+    fn play(&mut self, progress: &Progress, level: usize) -> Option<Choice> {
+        if !self.is_unlocked(progress, level) {
+            return None;
+        }
+        let (pack, path) = &self.packs[self.tab];
+        self.from_game = false;
+        Some(Choice::Play(pack.clone(), path.clone(), level))
+    }
+
+    /// The first level shown, the grid scrolls by pages for longer packs.
+    fn first_shown(&self) -> usize {
+        self.selected / (COLUMNS * ROWS) * (COLUMNS * ROWS)
+    }
+
+    fn tab_rect(&self, tab: usize) -> Rect {
+        let width = VIEW_WIDTH as i32 / self.packs.len() as i32;
+        Rect::new(
+            width * tab as i32 + 2,
+            GRID_Y - TAB_HEIGHT - 8,
+            width as u32 - 4,
+            TAB_HEIGHT as u32,
+        )
+    }
+
+    fn cell_rect(&self, level: usize) -> Rect {
+        let shown = level - self.first_shown();
+        Rect::new(
+            GRID_X + (shown % COLUMNS) as i32 * (CELL_WIDTH + CELL_GAP),
+            GRID_Y + (shown / COLUMNS) as i32 * (CELL_HEIGHT + CELL_GAP),
+            CELL_WIDTH as u32,
+            CELL_HEIGHT as u32,
+        )
+    }
+
+    /// The level shown under a point of the grid.
+    fn level_at(&self, x: i32, y: i32) -> Option<usize> {
+        let count = self.packs[self.tab].0.levels.len();
+        let first = self.first_shown();
+        (first..count.min(first + COLUMNS * ROWS))
+            .find(|level| self.cell_rect(*level).contains_point((x, y)))
+    }
+
+    fn show_keys(&mut self) {
+        self.keys_back = self.page;
+        self.page = Page::Keys;
+        self.waiting_key = false;
+    }
+
+    pub fn handle(
+        &mut self,
+        event: &Event,
+        progress: &Progress,
+        bindings: &mut Bindings,
+    ) -> Option<Choice> {
+        if self.page == Page::Keys {
+            return self.handle_keys(event, bindings);
+        }
+        if self.page == Page::Title {
+            return match *event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => Some(Choice::Quit),
+                Event::KeyDown {
+                    keycode: Some(Keycode::K),
+                    ..
+                } => {
+                    self.show_keys();
+                    None
+                }
+                Event::KeyDown {
+                    keycode: Some(code),
+                    ..
+                } if bindings.action(code) == Some(Action::Quit) => Some(Choice::Quit),
+                Event::KeyDown {
+                    keycode: Some(code),
+                    ..
+                } if bindings.action(code) == Some(Action::Fullscreen) => Some(Choice::Fullscreen),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return | Keycode::KpEnter | Keycode::Space),
+                    ..
+                }
+                | Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    ..
+                } => {
+                    self.page = Page::Levels;
+                    None
+                }
+                _ => None,
+            };
+        }
+
+        let count = self.packs[self.tab].0.levels.len();
+        match *event {
+            Event::KeyDown {
+                keycode: Some(code),
+                ..
+            } => match code {
+                Keycode::Escape => return self.back(),
+                Keycode::Left | Keycode::A => self.selected = self.selected.saturating_sub(1),
+                Keycode::Right | Keycode::D => self.selected = (self.selected + 1).min(count - 1),
+                Keycode::Up | Keycode::W => {
+                    self.selected = self.selected.saturating_sub(COLUMNS);
+                }
+                Keycode::Down | Keycode::S if self.selected + COLUMNS < count => {
+                    self.selected += COLUMNS;
+                }
+                Keycode::Tab | Keycode::PageDown => {
+                    self.tab = (self.tab + 1) % self.packs.len();
+                    self.selected = 0;
+                }
+                Keycode::PageUp => {
+                    self.tab = (self.tab + self.packs.len() - 1) % self.packs.len();
+                    self.selected = 0;
+                }
+                Keycode::Return | Keycode::KpEnter | Keycode::Space => {
+                    return self.play(progress, self.selected);
+                }
+                Keycode::K => self.show_keys(),
+                _ if bindings.action(code) == Some(Action::Quit) => return Some(Choice::Quit),
+                _ if bindings.action(code) == Some(Action::Fullscreen) => {
+                    return Some(Choice::Fullscreen);
+                }
+                _ => {}
+            },
+            Event::MouseMotion { x, y, .. } => {
+                if let Some(level) = self.level_at(x, y) {
+                    self.selected = level;
+                }
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                if let Some(tab) =
+                    (0..self.packs.len()).find(|tab| self.tab_rect(*tab).contains_point((x, y)))
+                {
+                    self.tab = tab;
+                    self.selected = 0;
+                } else if let Some(level) = self.level_at(x, y) {
+                    self.selected = level;
+                    return self.play(progress, level);
+                }
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Right,
+                ..
+            } => return self.back(),
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_keys(&mut self, event: &Event, bindings: &mut Bindings) -> Option<Choice> {
+        let action = Action::ALL[self.action];
+        match *event {
+            Event::KeyDown {
+                keycode: Some(code),
+                ..
+            } if self.waiting_key => {
+                self.waiting_key = false;
+                if code != Keycode::Escape {
+                    bindings.add(action, code);
+                    return Some(Choice::KeysChanged);
+                }
+            }
+            Event::KeyDown {
+                keycode: Some(code),
+                ..
+            } => match code {
+                Keycode::Escape => self.page = self.keys_back,
+                Keycode::Up | Keycode::W => self.action = self.action.saturating_sub(1),
+                Keycode::Down | Keycode::S => {
+                    self.action = (self.action + 1).min(Action::ALL.len() - 1);
+                }
+                Keycode::Return | Keycode::KpEnter | Keycode::Space => self.waiting_key = true,
+                Keycode::Delete | Keycode::Backspace => {
+                    bindings.clear(action);
+                    return Some(Choice::KeysChanged);
+                }
+                _ => {}
+            },
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } if !self.waiting_key => {
+                if let Some(action) = (0..Action::ALL.len())
+                    .find(|action| keys_row_rect(*action).contains_point((x, y)))
+                {
+                    self.action = action;
+                    self.waiting_key = true;
+                }
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Right,
+                ..
+            } if !self.waiting_key => self.page = self.keys_back,
+            _ => {}
+        }
+        None
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        title: &Texture,
+        logo: Option<&Texture>,
+        progress: &Progress,
+        bindings: &Bindings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // The title picture is 240x320, scaled up to the height of the view
+        let width = VIEW_HEIGHT * 240 / 320;
+        canvas.copy(
+            title,
+            None,
+            Some(Rect::new(
+                (VIEW_WIDTH as i32 - width as i32) / 2,
+                0,
+                width,
+                VIEW_HEIGHT,
+            )),
+        )?;
+
+        if self.page == Page::Title {
+            if let Some(logo) = logo {
+                let query = logo.query();
+                canvas.copy(
+                    logo,
+                    None,
+                    Some(Rect::new(
+                        (VIEW_WIDTH as i32 - query.width as i32) / 2,
+                        8,
+                        query.width,
+                        query.height,
+                    )),
+                )?;
+            }
+            let message = "Press Enter, K for keys";
+            let width = 8 * message.len() as i32 + 8;
+            let x = (VIEW_WIDTH as i32 - width) / 2;
+            let y = VIEW_HEIGHT as i32 - 32;
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+            canvas.fill_rect(Rect::new(x, y, width as u32, 16))?;
+            canvas.string(
+                (x + 4) as i16,
+                (y + 4) as i16,
+                message,
+                Color::RGB(255, 255, 255),
+            )?;
+            return Ok(());
+        }
+        if self.page == Page::Keys {
+            return self.draw_keys(canvas, bindings);
+        }
+
+        for (tab, (pack, _)) in self.packs.iter().enumerate() {
+            let rect = self.tab_rect(tab);
+            let name = truncate(&pack.name, rect.width() as usize / 8 - 1);
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+            canvas.fill_rect(rect)?;
+            let color = if tab == self.tab {
+                canvas.set_draw_color(Color::RGB(255, 200, 0));
+                canvas.draw_rect(rect)?;
+                Color::RGB(255, 200, 0)
+            } else {
+                Color::RGB(255, 255, 255)
+            };
+            canvas.string(
+                (rect.x() + (rect.width() as i32 - 8 * name.chars().count() as i32) / 2) as i16,
+                (rect.y() + 4) as i16,
+                &name,
+                color,
+            )?;
+        }
+
+        let levels = &self.packs[self.tab].0.levels;
+        let first = self.first_shown();
+        for (level, shown) in levels.iter().enumerate().skip(first).take(COLUMNS * ROWS) {
+            let rect = self.cell_rect(level);
+            let unlocked = self.is_unlocked(progress, level);
+            let best = progress.best.get(&shown.id());
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, if unlocked { 200 } else { 120 }));
+            canvas.fill_rect(rect)?;
+            if level == self.selected {
+                canvas.set_draw_color(Color::RGB(255, 200, 0));
+                canvas.draw_rect(rect)?;
+            }
+            let number = format!("{}", level + 1);
+            let (status, color) = match best {
+                // the time doesn't compare with a run without undos
+                Some(best) if best.undos > 0 => (
+                    format!("{}*", format_time(best.frames)),
+                    Color::RGB(120, 220, 80),
+                ),
+                Some(best) => (format_time(best.frames), Color::RGB(120, 220, 80)),
+                None if unlocked => ("-".to_string(), Color::RGB(255, 255, 255)),
+                None => ("locked".to_string(), Color::RGB(128, 128, 128)),
+            };
+            canvas.string(
+                (rect.x() + (CELL_WIDTH - 8 * number.len() as i32) / 2) as i16,
+                (rect.y() + 8) as i16,
+                &number,
+                color,
+            )?;
+            canvas.string(
+                (rect.x() + (CELL_WIDTH - 8 * status.len() as i32) / 2) as i16,
+                (rect.y() + 24) as i16,
+                &status,
+                color,
+            )?;
+        }
+
+        let level = &levels[self.selected];
+        let info = match progress.best.get(&level.id()) {
+            Some(best) if best.undos > 0 => format!(
+                "{}: best {} with undo",
+                level.name,
+                format_time(best.frames)
+            ),
+            Some(best) => format!("{}: best {}", level.name, format_time(best.frames)),
+            None if self.is_unlocked(progress, self.selected) => level.name.clone(),
+            None => format!("{}: locked", level.name),
+        };
+        let lines = [info.as_str(), "Enter play Tab pack K keys Esc back"];
+        let y = GRID_Y + ROWS as i32 * (CELL_HEIGHT + CELL_GAP) + 4;
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+        canvas.fill_rect(Rect::new(
+            GRID_X,
+            y,
+            (COLUMNS as i32 * (CELL_WIDTH + CELL_GAP) - CELL_GAP) as u32,
+            12 * lines.len() as u32 + 8,
+        ))?;
+        for (idx, line) in lines.iter().enumerate() {
+            canvas.string(
+                (GRID_X + 4) as i16,
+                (y + 4 + 12 * idx as i32) as i16,
+                &truncate(line, 36),
+                Color::RGB(255, 255, 255),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Each action with its keys, the keys bound to several actions in red.
+    fn draw_keys(
+        &self,
+        canvas: &mut WindowCanvas,
+        bindings: &Bindings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let conflicts = bindings.conflicts();
+        let mut footer = Vec::new();
+        for (code, actions) in &conflicts {
+            let names = actions
+                .iter()
+                .map(|action| action.name())
+                .collect::<Vec<_>>();
+            footer.push(format!("{} is {}", code.name(), names.join(", ")));
+        }
+        footer.push(String::new());
+        if self.waiting_key {
+            footer.push(format!(
+                "Press a key for {}",
+                Action::ALL[self.action].name()
+            ));
+            footer.push("Esc cancels".to_string());
+        } else {
+            footer.push("Enter add, Del clear, Esc back".to_string());
+        }
+
+        let top = keys_row_rect(0).y() - 20;
+        let bottom = keys_row_rect(Action::ALL.len() - 1).bottom() + 4 + 12 * footer.len() as i32;
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+        canvas.fill_rect(Rect::new(
+            GRID_X,
+            top,
+            VIEW_WIDTH - 2 * GRID_X as u32,
+            (bottom - top + 4) as u32,
+        ))?;
+        canvas.string(
+            (GRID_X + 4) as i16,
+            (top + 6) as i16,
+            "Keys",
+            Color::RGB(255, 255, 255),
+        )?;
+        for (idx, action) in Action::ALL.into_iter().enumerate() {
+            let rect = keys_row_rect(idx);
+            let name_color = if idx == self.action {
+                Color::RGB(255, 200, 0)
+            } else {
+                Color::RGB(255, 255, 255)
+            };
+            canvas.string(rect.x() as i16, rect.y() as i16, action.name(), name_color)?;
+            // 12 characters for the name, then the keys one after the other
+            let mut x = rect.x() + 8 * 12;
+            for code in bindings.keys(action) {
+                let name = code.name();
+                let color = if conflicts.iter().any(|(other, _)| other == code) {
+                    Color::RGB(255, 80, 80)
+                } else {
+                    name_color
+                };
+                if x + 8 * name.chars().count() as i32 > rect.right() {
+                    canvas.string(x as i16, rect.y() as i16, "...", name_color)?;
+                    break;
+                }
+                canvas.string(x as i16, rect.y() as i16, &name, color)?;
+                x += 8 * (name.chars().count() as i32 + 1);
+            }
+        }
+        let y = keys_row_rect(Action::ALL.len() - 1).bottom() + 4;
+        for (idx, line) in footer.iter().enumerate() {
+            let color = if idx < conflicts.len() {
+                Color::RGB(255, 80, 80)
+            } else {
+                Color::RGB(255, 255, 255)
+            };
+            canvas.string(
+                (GRID_X + 4) as i16,
+                (y + 12 * idx as i32) as i16,
+                &truncate(line, 36),
+                color,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The line of an action on the keys page.
+fn keys_row_rect(action: usize) -> Rect {
+    Rect::new(
+        GRID_X + 4,
+        GRID_Y + 12 * action as i32,
+        VIEW_WIDTH - 2 * (GRID_X as u32 + 4),
+        12,
+    )
+}
+
+/// The first `len` characters of `text`, to fit a box of the 8px font.
+fn truncate(text: &str, len: usize) -> String {
+    text.chars().take(len).collect()
+}
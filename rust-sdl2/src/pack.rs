@@ -0,0 +1,222 @@
+use std::path::{Path, PathBuf};
+
+use crate::key_value;
+use crate::map::{Goal, Map, MapInfo, EGG_LEVELS, NORMAL_LEVELS};
+
+/// An ordered list of levels played as a campaign.
+///
+/// A pack manifest is a text file of `key = value` lines, `#` at the start
+/// of a line or after a space starts a comment, and level paths are relative
+/// to the manifest:
+///
+/// ```text
+/// name = Internal Puzzles
+/// level = carrots first.blm
+/// level = eggs second.blm
+/// ```
+#[derive(Debug, Clone)]
+pub struct LevelPack {
+    pub name: String,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub name: String,
+    pub source: LevelSource,
+    pub goal: Goal,
+}
+
+#[derive(Debug, Clone)]
+pub enum LevelSource {
+    Builtin(Map),
+    File(PathBuf),
+}
+
+impl Level {
+    /// The built-in map, or the path of the level file.
+    pub fn id(&self) -> String {
+        match &self.source {
+            LevelSource::Builtin(map) => map.to_string(),
+            LevelSource::File(path) => path.display().to_string(),
+        }
+    }
+
+    pub fn load_map_info(&self) -> Result<MapInfo, Box<dyn std::error::Error>> {
+        let mut map_info = match &self.source {
+            LevelSource::Builtin(map) => map.load_map_info()?,
+            LevelSource::File(path) => MapInfo::load(path)?,
+        };
+        map_info.goal = self.goal;
+        Ok(map_info)
+    }
+}
+
+impl LevelPack {
+    /// The 30 built-in carrot levels.
+    pub fn normal() -> LevelPack {
+        LevelPack::builtin(
+            "Normal",
+            (1..=NORMAL_LEVELS).map(Map::Normal),
+            Goal::Carrots,
+        )
+    }
+
+    /// The 20 built-in egg levels.
+    pub fn egg() -> LevelPack {
+        LevelPack::builtin("Egg", (1..=EGG_LEVELS).map(Map::Egg), Goal::Eggs)
+    }
+
+    fn builtin(name: &str, maps: impl Iterator<Item = Map>, goal: Goal) -> LevelPack {
+        let levels = maps
+            .map(|map| Level {
+                name: map.to_string(),
+                source: LevelSource::Builtin(map),
+                goal,
+            })
+            .collect();
+        LevelPack {
+            name: name.to_string(),
+            levels,
+        }
+    }
+
+    /// The built-in pack a level belongs to, and its index in that pack.
+    pub fn containing(map: Map) -> Result<(LevelPack, usize), Box<dyn std::error::Error>> {
+        let (pack, num) = match map {
+            Map::Normal(num) => (LevelPack::normal(), num),
+            Map::Egg(num) => (LevelPack::egg(), num),
+        };
+        if num == 0 || num as usize > pack.levels.len() {
+            return Err(format!("Invalid {} level: {}", pack.name.to_lowercase(), num).into());
+        }
+        Ok((pack, num as usize - 1))
+    }
+
+    /// A pack made of a single `.blm` file, the goal is guessed from its
+    /// content.
+    pub fn single(path: &Path) -> Result<LevelPack, Box<dyn std::error::Error>> {
+        let map_info = MapInfo::load(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        Ok(LevelPack {
+            name: name.clone(),
+            levels: vec![Level {
+                name,
+                source: LevelSource::File(path.to_path_buf()),
+                goal: map_info.goal,
+            }],
+        })
+    }
+
+    /// Read a pack manifest.
+    pub fn load(path: &Path) -> Result<LevelPack, Box<dyn std::error::Error>> {
+        let content = key_value::read(path)?;
+        Ok(LevelPack::parse(path, &content)?)
+    }
+
+    /// Parse `content`, the text of the pack manifest at `path`.
+    fn parse(path: &Path, content: &str) -> Result<LevelPack, String> {
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut name = None;
+        let mut levels = Vec::new();
+        for line in key_value::lines(path, content) {
+            let (key, value) = line.key_value()?;
+            match key {
+                "name" => name = Some(value.to_string()),
+                "level" => {
+                    let (goal_str, file) = value
+                        .split_once(char::is_whitespace)
+                        .map(|(goal, file)| (goal, file.trim()))
+                        .ok_or_else(|| {
+                            line.error(format!("Expected `level = <goal> <file>`, got: {value}"))
+                        })?;
+                    let goal = match goal_str {
+                        "carrots" => Goal::Carrots,
+                        "eggs" => Goal::Eggs,
+                        _ => return Err(line.error(format!("Invalid goal: {goal_str}"))),
+                    };
+                    let file = base_dir.join(file);
+                    let name = file
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| file.display().to_string());
+                    levels.push(Level {
+                        name,
+                        source: LevelSource::File(file),
+                        goal,
+                    });
+                }
+                _ => return Err(line.error(format!("Unknown key: {key}"))),
+            }
+        }
+        let name = name.ok_or_else(|| format!("{}: Missing pack name", path.display()))?;
+        if levels.is_empty() {
+            return Err(format!("{}: The pack has no level", path.display()));
+        }
+        Ok(LevelPack { name, levels })
+    }
+
+    /// The level after `index`, wrapping around at the end of the pack.
+    pub fn next(&self, index: usize) -> usize {
+        (index + 1) % self.levels.len()
+    }
+
+    /// The level before `index`, wrapping around at the start of the pack.
+    pub fn previous(&self, index: usize) -> usize {
+        (index + self.levels.len() - 1) % self.levels.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn level_files_may_have_a_hash_in_their_name() {
+        let dir = std::env::temp_dir().join(format!("bobby-pack-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pack.txt");
+        fs::write(
+            &path,
+            "# made by hand\nname = Hashes\nlevel = carrots level#1.blm # first\n",
+        )
+        .unwrap();
+        let pack = LevelPack::load(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        let pack = pack.unwrap();
+        assert_eq!(pack.name, "Hashes");
+        assert_eq!(pack.levels[0].name, "level#1");
+        assert_eq!(
+            pack.levels[0].id(),
+            dir.join("level#1.blm").display().to_string()
+        );
+    }
+
+    #[test]
+    fn a_malformed_level_is_an_error() {
+        let path = Path::new("pack.txt");
+        let err = LevelPack::parse(path, "name = Pack\nlevel = first.blm\n").unwrap_err();
+        assert_eq!(
+            err,
+            "pack.txt:2: Expected `level = <goal> <file>`, got: first.blm"
+        );
+        let err = LevelPack::parse(path, "name = Pack\nlevel = keys first.blm\n").unwrap_err();
+        assert_eq!(err, "pack.txt:2: Invalid goal: keys");
+        let err = LevelPack::parse(path, "name = Pack\n").unwrap_err();
+        assert_eq!(err, "pack.txt: The pack has no level");
+    }
+
+    #[test]
+    fn an_unknown_key_is_an_error() {
+        let content = "name = Pack\nauthor = Me\nlevel = carrots first.blm\n";
+        assert_eq!(
+            LevelPack::parse(Path::new("pack.txt"), content).unwrap_err(),
+            "pack.txt:2: Unknown key: author"
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use sdl2::{
+    event::Event, gfx::primitives::DrawRenderer, keyboard::Keycode, mouse::MouseButton,
+    pixels::Color, rect::Rect, render::WindowCanvas,
+};
+
+use crate::bindings::{Action, Bindings};
+use crate::settings::Settings;
+
+const WIDTH: i32 = 200;
+const ROW_HEIGHT: i32 = 16;
+/// The title of the box, above the rows.
+const HEADER_HEIGHT: i32 = 24;
+
+/// What the pause menu asks of the game.
+pub enum PauseChoice {
+    Resume,
+    Restart,
+    /// Back to the level-select menu, leaving it returns to the paused game.
+    Levels,
+    /// The settings were changed and are to be saved.
+    SettingsChanged,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Item {
+    Resume,
+    Restart,
+    Levels,
+    Settings,
+    Quit,
+}
+
+impl Item {
+    const ALL: [Item; 5] = [
+        Item::Resume,
+        Item::Restart,
+        Item::Levels,
+        Item::Settings,
+        Item::Quit,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Item::Resume => "Resume",
+            Item::Restart => "Restart",
+            Item::Levels => "Levels",
+            Item::Settings => "Settings",
+            Item::Quit => "Quit",
+        }
+    }
+}
+
+/// The settings that can be changed, then a row to go back.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Setting {
+    DeadZone,
+    LookAhead,
+    Easing,
+    Back,
+}
+
+impl Setting {
+    const ALL: [Setting; 4] = [
+        Setting::DeadZone,
+        Setting::LookAhead,
+        Setting::Easing,
+        Setting::Back,
+    ];
+
+    fn label(self, settings: &Settings) -> String {
+        let camera = &settings.camera;
+        match self {
+            Setting::DeadZone => format!("Dead zone  {:>5}", camera.dead_zone),
+            Setting::LookAhead => format!("Look ahead {:>5}", camera.look_ahead),
+            Setting::Easing => format!("Easing     {:>4.2}s", camera.easing),
+            Setting::Back => "Back".to_string(),
+        }
+    }
+
+    /// Turn the setting one step up, or down for a negative `steps`.
+    fn change(self, settings: &mut Settings, steps: i32) -> bool {
+        let camera = &mut settings.camera;
+        match self {
+            Setting::DeadZone => {
+                camera.dead_zone = (camera.dead_zone as i32 + 8 * steps).clamp(0, 160) as u32;
+            }
+            Setting::LookAhead => {
+                camera.look_ahead = (camera.look_ahead as i32 + 8 * steps).clamp(0, 160) as u32;
+            }
+            Setting::Easing => {
+                // in hundredths, so that the steps add up exactly
+                let hundredths = (camera.easing * 100.0).round() as i32 + 5 * steps;
+                camera.easing = hundredths.clamp(0, 100) as f32 / 100.0;
+            }
+            Setting::Back => return false,
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Page {
+    Items,
+    Settings,
+}
+
+/// The menu over a paused level. The game doesn't run while it is shown.
+pub struct PauseMenu {
+    page: Page,
+    /// The row selected on the page, in [`Item::ALL`] or [`Setting::ALL`].
+    selected: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> PauseMenu {
+        PauseMenu {
+            page: Page::Items,
+            selected: 0,
+        }
+    }
+
+    /// Show the menu from its first item on.
+    pub fn open(&mut self) {
+        self.page = Page::Items;
+        self.selected = 0;
+    }
+
+    fn rows(&self) -> usize {
+        match self.page {
+            Page::Items => Item::ALL.len(),
+            Page::Settings => Setting::ALL.len(),
+        }
+    }
+
+    /// The box of the menu in a view of `width` x `height` points.
+    fn rect(&self, (width, height): (u32, u32)) -> Rect {
+        let box_height = HEADER_HEIGHT + ROW_HEIGHT * self.rows() as i32 + 8;
+        Rect::new(
+            (width as i32 - WIDTH) / 2,
+            (height as i32 - box_height) / 2,
+            WIDTH as u32,
+            box_height as u32,
+        )
+    }
+
+    fn row_rect(&self, view: (u32, u32), row: usize) -> Rect {
+        let rect = self.rect(view);
+        Rect::new(
+            rect.x() + 4,
+            rect.y() + HEADER_HEIGHT + ROW_HEIGHT * row as i32,
+            rect.width() - 8,
+            ROW_HEIGHT as u32,
+        )
+    }
+
+    fn row_at(&self, view: (u32, u32), x: i32, y: i32) -> Option<usize> {
+        (0..self.rows()).find(|row| self.row_rect(view, *row).contains_point((x, y)))
+    }
+
+    /// Act on the selected row.
+    fn choose(&mut self) -> Option<PauseChoice> {
+        match self.page {
+            Page::Items => match Item::ALL[self.selected] {
+                Item::Resume => Some(PauseChoice::Resume),
+                Item::Restart => Some(PauseChoice::Restart),
+                Item::Levels => Some(PauseChoice::Levels),
+                Item::Settings => {
+                    self.page = Page::Settings;
+                    self.selected = 0;
+                    None
+                }
+                Item::Quit => Some(PauseChoice::Quit),
+            },
+            Page::Settings => {
+                if Setting::ALL[self.selected] == Setting::Back {
+                    self.back();
+                }
+                None
+            }
+        }
+    }
+
+    fn back(&mut self) -> Option<PauseChoice> {
+        match self.page {
+            Page::Items => Some(PauseChoice::Resume),
+            Page::Settings => {
+                self.page = Page::Items;
+                self.selected = Item::Settings as usize;
+                None
+            }
+        }
+    }
+
+    fn change(&mut self, settings: &mut Settings, steps: i32) -> Option<PauseChoice> {
+        if self.page == Page::Settings && Setting::ALL[self.selected].change(settings, steps) {
+            return Some(PauseChoice::SettingsChanged);
+        }
+        None
+    }
+
+    /// Handle an event with mouse positions in a view of `view` points.
+    pub fn handle(
+        &mut self,
+        event: &Event,
+        settings: &mut Settings,
+        bindings: &Bindings,
+        view: (u32, u32),
+    ) -> Option<PauseChoice> {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(code),
+                ..
+            } => match code {
+                Keycode::Escape => return self.back(),
+                Keycode::Up | Keycode::W => self.selected = self.selected.saturating_sub(1),
+                Keycode::Down | Keycode::S => {
+                    self.selected = (self.selected + 1).min(self.rows() - 1);
+                }
+                Keycode::Left | Keycode::A => return self.change(settings, -1),
+                Keycode::Right | Keycode::D => return self.change(settings, 1),
+                Keycode::Return | Keycode::KpEnter | Keycode::Space => return self.choose(),
+                _ => match bindings.action(code) {
+                    Some(Action::Pause) => return Some(PauseChoice::Resume),
+                    Some(Action::Quit) => return Some(PauseChoice::Quit),
+                    _ => {}
+                },
+            },
+            Event::MouseMotion { x, y, .. } => {
+                if let Some(row) = self.row_at(view, x, y) {
+                    self.selected = row;
+                }
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                if let Some(row) = self.row_at(view, x, y) {
+                    self.selected = row;
+                    return self.choose();
+                }
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Right,
+                ..
+            } => return self.back(),
+            Event::MouseWheel { y, .. } if y != 0 => return self.change(settings, y.signum()),
+            _ => {}
+        }
+        None
+    }
+
+    /// Draw the menu over a view of `view` points, its top left corner at
+    /// `(x_offset, y_offset)`.
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        settings: &Settings,
+        view: (u32, u32),
+        (x_offset, y_offset): (i32, i32),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (title, rows, footer) = match self.page {
+            Page::Items => (
+                "Paused",
+                Item::ALL
+                    .iter()
+                    .map(|item| item.label().to_string())
+                    .collect::<Vec<_>>(),
+                "",
+            ),
+            Page::Settings => (
+                "Camera",
+                Setting::ALL
+                    .iter()
+                    .map(|setting| setting.label(settings))
+                    .collect(),
+                "Left/Right change",
+            ),
+        };
+        let mut rect = self.rect(view);
+        rect.offset(x_offset, y_offset);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+        canvas.fill_rect(rect)?;
+        canvas.string(
+            (rect.x() + (WIDTH - 8 * title.len() as i32) / 2) as i16,
+            (rect.y() + 8) as i16,
+            title,
+            Color::RGB(255, 255, 255),
+        )?;
+        for (idx, row) in rows.iter().enumerate() {
+            let mut row_rect = self.row_rect(view, idx);
+            row_rect.offset(x_offset, y_offset);
+            let color = if idx == self.selected {
+                canvas.set_draw_color(Color::RGB(255, 200, 0));
+                canvas.draw_rect(row_rect)?;
+                Color::RGB(255, 200, 0)
+            } else {
+                Color::RGB(255, 255, 255)
+            };
+            canvas.string(
+                (row_rect.x() + 8) as i16,
+                (row_rect.y() + 4) as i16,
+                row,
+                color,
+            )?;
+        }
+        if !footer.is_empty() {
+            let y = rect.bottom() + 4;
+            let width = 8 * footer.len() as i32 + 8;
+            let x = rect.x() + (WIDTH - width) / 2;
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+            canvas.fill_rect(Rect::new(x, y, width as u32, 16))?;
+            canvas.string(
+                (x + 4) as i16,
+                (y + 4) as i16,
+                footer,
+                Color::RGB(255, 255, 255),
+            )?;
+        }
+        Ok(())
+    }
+}
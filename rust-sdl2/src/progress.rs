@@ -0,0 +1,204 @@
+//! What the player achieved so far, kept between two runs of the game.
+//!
+//! The save file is a text file of `key = value` lines, levels are named by
+//! [`crate::Level::id`] and times are counted in frames:
+//!
+//! ```text
+//! last = Normal-12
+//! pack = packs/internal.pack
+//! best = Normal-01 1260 0
+//! best = Normal-02 2345 3
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::key_value;
+
+/// The best clear of a level.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Clear {
+    pub frames: u32,
+    /// Moves undone during the run.
+    pub undos: usize,
+}
+
+impl Clear {
+    /// A clear without undo beats any clear with some, whatever the time.
+    pub fn is_better_than(&self, other: &Clear) -> bool {
+        (self.undos > 0, self.frames) < (other.undos > 0, other.frames)
+    }
+}
+
+/// `bobby-carrot` in the XDG data directory, where the files kept between
+/// two runs of the game go.
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// `bobby-carrot` in the XDG config directory, where the settings go.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `bobby-carrot` in the directory set by `var`, or else in `default` in the
+/// home directory.
+fn xdg_dir(var: &str, default: &str) -> Option<PathBuf> {
+    let base = env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(default)))?;
+    Some(base.join("bobby-carrot"))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    /// The level played last, to resume from.
+    pub last_level: Option<String>,
+    /// The manifest of the pack [`Progress::last_level`] belongs to, if it
+    /// isn't a built-in level.
+    pub last_pack: Option<PathBuf>,
+    /// The best clear of each level cleared at least once.
+    pub best: BTreeMap<String, Clear>,
+}
+
+impl Progress {
+    /// `save.txt` in the [`data_dir`].
+    pub fn default_path() -> Option<PathBuf> {
+        Some(data_dir()?.join("save.txt"))
+    }
+
+    /// Read a save file, a missing one is an empty progress.
+    pub fn load(path: &Path) -> Result<Progress, Box<dyn std::error::Error>> {
+        match key_value::read_if_exists(path)? {
+            Some(content) => Ok(Progress::parse(path, &content)?),
+            None => Ok(Progress::default()),
+        }
+    }
+
+    /// Parse `content`, the text of the save file at `path`.
+    fn parse(path: &Path, content: &str) -> Result<Progress, String> {
+        let mut progress = Progress::default();
+        for line in key_value::lines(path, content) {
+            let (key, value) = line.key_value()?;
+            match key {
+                "last" => progress.last_level = Some(value.to_string()),
+                "pack" => progress.last_pack = Some(PathBuf::from(value)),
+                "best" => {
+                    // the level may be a path with spaces, the numbers can't
+                    let mut fields = value.rsplitn(3, ' ');
+                    let (Some(undos), Some(frames), Some(level)) =
+                        (fields.next(), fields.next(), fields.next())
+                    else {
+                        return Err(line.error(format!(
+                            "Expected `best = <level> <frames> <undos>`, got: {value}"
+                        )));
+                    };
+                    let clear = Clear {
+                        frames: frames
+                            .parse()
+                            .map_err(|e| line.error(format!("Invalid time {frames}: {e}")))?,
+                        undos: undos.parse().map_err(|e| {
+                            line.error(format!("Invalid number of undos {undos}: {e}"))
+                        })?,
+                    };
+                    progress.best.insert(level.trim().to_string(), clear);
+                }
+                _ => return Err(line.error(format!("Unknown key: {key}"))),
+            }
+        }
+        Ok(progress)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(key_value::write(path, &self.to_text())?)
+    }
+
+    /// The text of the save file.
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        if let Some(level) = &self.last_level {
+            text.push_str(&format!("last = {level}\n"));
+        }
+        if let Some(pack) = &self.last_pack {
+            text.push_str(&format!("pack = {}\n", pack.display()));
+        }
+        for (level, clear) in &self.best {
+            text.push_str(&format!(
+                "best = {level} {} {}\n",
+                clear.frames, clear.undos
+            ));
+        }
+        text
+    }
+
+    pub fn is_cleared(&self, level: &str) -> bool {
+        self.best.contains_key(level)
+    }
+
+    /// Count a clear of `level`, returns whether it is a new best.
+    pub fn record_clear(&mut self, level: &str, clear: Clear) -> bool {
+        match self.best.get(level) {
+            Some(best) if !clear.is_better_than(best) => false,
+            _ => {
+                self.best.insert(level.to_string(), clear);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Progress, String> {
+        Progress::parse(Path::new("save.txt"), content)
+    }
+
+    #[test]
+    fn a_malformed_line_is_an_error() {
+        assert_eq!(
+            parse("last = Normal-01\nbest = Normal-01\n").unwrap_err(),
+            "save.txt:2: Expected `best = <level> <frames> <undos>`, got: Normal-01"
+        );
+        let err = parse("best = Normal-01 1260 -1\n").unwrap_err();
+        assert!(
+            err.starts_with("save.txt:1: Invalid number of undos -1"),
+            "{err}"
+        );
+        let err = parse("\nlast Normal-01\n").unwrap_err();
+        assert!(
+            err.starts_with("save.txt:2: Expected `key = value`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn an_unknown_key_is_an_error() {
+        assert_eq!(
+            parse("lats = Normal-01\n").unwrap_err(),
+            "save.txt:1: Unknown key: lats"
+        );
+    }
+
+    #[test]
+    fn saved_progress_loads_back() {
+        let mut progress = Progress {
+            last_level: Some("packs/my levels/one.blm".to_string()),
+            last_pack: Some(PathBuf::from("packs/my levels/pack.txt")),
+            ..Progress::default()
+        };
+        let clear = Clear {
+            frames: 1260,
+            undos: 3,
+        };
+        progress.record_clear("Normal-01", clear);
+        progress.record_clear("packs/my levels/one.blm", clear);
+        let loaded = parse(&progress.to_text()).unwrap();
+        assert_eq!(loaded.last_level, progress.last_level);
+        assert_eq!(loaded.last_pack, progress.last_pack);
+        assert_eq!(loaded.best, progress.best);
+    }
+}
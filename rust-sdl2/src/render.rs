@@ -0,0 +1,93 @@
+use std::path::Path;
+use std::time::Duration;
+
+use bobby_carrot::{Bobby, State};
+use sdl2::{
+    image::SaveSurface,
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::BlendMode,
+    surface::Surface,
+};
+
+use crate::camera::{Camera, CameraSettings};
+use crate::{draw_bobby, draw_hud, draw_tiles, find_level, Assets, VIEW_HEIGHT, VIEW_WIDTH};
+
+/// `render <level> <output.png> [--viewport] [--hud]`: draws a built-in level
+/// (`normal-12`) or a level file to a PNG, with Bobby on the start. It is
+/// drawn in memory by the software renderer of SDL, without a window.
+///
+/// The whole map is drawn unless `--viewport` is given, then only the view of
+/// the game as the level starts. `--hud` adds what is left to collect and the
+/// time over it.
+pub fn render(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: bobby-carrot render <level> <output.png> [--viewport] [--hud]";
+    let mut level = None;
+    let mut output = None;
+    let mut viewport = false;
+    let mut hud = false;
+    for arg in args {
+        match arg.as_str() {
+            "--viewport" => viewport = true,
+            "--hud" => hud = true,
+            _ if level.is_none() => level = Some(arg.as_str()),
+            _ if output.is_none() => output = Some(Path::new(arg)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let (level, output) = level.zip(output).ok_or(USAGE)?;
+    let (pack, index) = find_level(level)?;
+    let map_info = pack.levels[index].load_map_info()?;
+
+    // standing still rather than fading in, as the level starts
+    let mut bobby = Bobby::new(0, map_info.coord_start);
+    bobby.state = State::Idle;
+    let map_size = (32 * map_info.data.width(), 32 * map_info.data.height());
+    let (width, height, x, y) = if viewport {
+        let (x, y) = map_info.coord_start;
+        let target = (32 * x as i32 + 16, 32 * y as i32 + 16);
+        let (x, y) = Camera::new(CameraSettings::default()).update(
+            target,
+            None,
+            Duration::ZERO,
+            (VIEW_WIDTH, VIEW_HEIGHT),
+            map_size,
+        );
+        (VIEW_WIDTH, VIEW_HEIGHT, x, y)
+    } else {
+        (map_size.0, map_size.1, 0, 0)
+    };
+
+    let surface = Surface::new(width, height, PixelFormatEnum::RGBA32)?;
+    let mut canvas = surface.into_canvas()?;
+    let texture_creator = canvas.texture_creator();
+    let assets = Assets::load_all(&texture_creator)?;
+    canvas.set_blend_mode(BlendMode::Blend);
+    // a map smaller than the view leaves the rest black, as in the game
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.set_viewport(Rect::new(
+        -x,
+        -y,
+        (width as i32 + x) as u32,
+        (height as i32 + y) as u32,
+    ));
+    draw_tiles(&mut canvas, &assets, &map_info.data, 0, false)?;
+    draw_bobby(&mut canvas, &assets, &bobby, 0, None, 0.0)?;
+    if hud {
+        draw_hud(
+            &mut canvas,
+            &assets,
+            &map_info,
+            &bobby,
+            0,
+            0,
+            Rect::new(x, y, width, height),
+        )?;
+    }
+    canvas
+        .into_surface()
+        .save(output)
+        .map_err(|err| format!("{}: {}", output.display(), err))?;
+    Ok(())
+}
@@ -0,0 +1,280 @@
+//! Recorded runs of one level, played back frame by frame.
+//!
+//! A replay file is a text file with a `key = value` header, then one line
+//! per run of identical inputs: the frame of the first one, the input, and
+//! for held directions the number of frames it was held. The level is
+//! looked up in the pack manifest of the header, if there is one.
+//!
+//! ```text
+//! version = 0.1.0
+//! pack = /home/me/levels/pack.txt
+//! level = /home/me/levels/first.blm
+//! 31 right 9
+//! 52 down 1
+//! 98 undo
+//! 130 restart
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use crate::game::Direction;
+use crate::key_value;
+
+/// What the player did before a [`crate::GameState::step`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Input {
+    /// The direction held for this step.
+    Hold(Direction),
+    /// The level was restarted before this step.
+    Restart,
+    /// The last move was undone before this step, see [`crate::UndoStack`].
+    Undo,
+}
+
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// Version of the game the replay was recorded with.
+    pub version: String,
+    /// The manifest of the pack the level was played in, `None` for a
+    /// built-in level or a level file played on its own.
+    pub pack: Option<PathBuf>,
+    /// The built-in map (`Normal-12`) or the level file played, see
+    /// [`crate::Level::id`].
+    pub level: String,
+    /// The runs of identical inputs in order: the value of
+    /// [`crate::GameState::frame`] when the first one was applied, the input
+    /// and the number of frames in a row it was applied.
+    pub inputs: Vec<(u32, Input, u32)>,
+}
+
+impl Replay {
+    pub fn new(level: &str, pack: Option<&Path>) -> Replay {
+        Replay {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            pack: pack.map(Path::to_path_buf),
+            level: level.to_string(),
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, frame: u32, input: Input) {
+        if let Some((start, last, count)) = self.inputs.last_mut() {
+            if matches!(input, Input::Hold(_)) && *last == input && *start + *count == frame {
+                *count += 1;
+                return;
+            }
+        }
+        self.inputs.push((frame, input, 1));
+    }
+
+    /// The inputs to apply while the game is at `frame`.
+    pub fn inputs_at(&self, frame: u32) -> impl Iterator<Item = Input> + '_ {
+        let end = self.inputs.partition_point(|(start, ..)| *start <= frame);
+        // the runs before are over, the ones after are still to come
+        let start = self.inputs[..end]
+            .iter()
+            .rposition(|(start, _, count)| start + count <= frame)
+            .map_or(0, |idx| idx + 1);
+        self.inputs[start..end].iter().map(|(_, input, _)| *input)
+    }
+
+    /// The frame after the last input.
+    pub fn end_frame(&self) -> u32 {
+        self.inputs
+            .last()
+            .map_or(0, |(start, _, count)| start + count)
+    }
+
+    /// Whether it was recorded by another version of the game, whose rules
+    /// may differ.
+    pub fn is_outdated(&self) -> bool {
+        self.version != env!("CARGO_PKG_VERSION")
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, Box<dyn std::error::Error>> {
+        let content = key_value::read(path)?;
+        Ok(Replay::parse(path, &content)?)
+    }
+
+    /// Parse `content`, the text of the replay file at `path`.
+    fn parse(path: &Path, content: &str) -> Result<Replay, String> {
+        let mut version = None;
+        let mut pack = None;
+        let mut level = None;
+        let mut inputs: Vec<(u32, Input, u32)> = Vec::new();
+        for line in key_value::lines(path, content) {
+            if line.text.contains('=') {
+                let (key, value) = line.key_value()?;
+                match key {
+                    "version" => version = Some(value.to_string()),
+                    "pack" => pack = Some(PathBuf::from(value)),
+                    "level" => level = Some(value.to_string()),
+                    _ => return Err(line.error(format!("Unknown key: {key}"))),
+                }
+                continue;
+            }
+
+            let fields = line.text.split_whitespace().collect::<Vec<_>>();
+            let (frame, input, count) = match fields[..] {
+                [frame, "restart"] => (frame, Input::Restart, "1"),
+                [frame, "undo"] => (frame, Input::Undo, "1"),
+                [frame, direction, count] => {
+                    let direction = match direction {
+                        "left" => Direction::Left,
+                        "right" => Direction::Right,
+                        "up" => Direction::Up,
+                        "down" => Direction::Down,
+                        _ => return Err(line.error(format!("Invalid direction: {direction}"))),
+                    };
+                    (frame, Input::Hold(direction), count)
+                }
+                _ => {
+                    return Err(line.error(format!(
+                        "Expected `<frame> <direction> <frames>`, `<frame> restart` or `<frame> undo`, got: {}",
+                        line.text
+                    )))
+                }
+            };
+            let frame: u32 = frame
+                .parse()
+                .map_err(|e| line.error(format!("Invalid frame {frame}: {e}")))?;
+            let count: u32 = count
+                .parse()
+                .map_err(|e| line.error(format!("Invalid number of frames {count}: {e}")))?;
+            if inputs
+                .last()
+                .is_some_and(|(start, _, count)| start + count - 1 > frame)
+            {
+                return Err(line.error(format!("Frame {frame} is before the previous input")));
+            }
+            if frame.checked_add(count).is_none() {
+                return Err(
+                    line.error(format!("Frame {frame} held for {count} frames is too late"))
+                );
+            }
+            if count > 0 {
+                inputs.push((frame, input, count));
+            }
+        }
+        Ok(Replay {
+            version: version.ok_or_else(|| format!("{}: Missing version", path.display()))?,
+            pack,
+            level: level.ok_or_else(|| format!("{}: Missing level", path.display()))?,
+            inputs,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(key_value::write(path, &self.to_text())?)
+    }
+
+    /// The text of the replay file.
+    fn to_text(&self) -> String {
+        let mut text = format!("version = {}\n", self.version);
+        if let Some(pack) = &self.pack {
+            text.push_str(&format!("pack = {}\n", pack.display()));
+        }
+        text.push_str(&format!("level = {}\n", self.level));
+        for (frame, input, count) in &self.inputs {
+            match input {
+                Input::Restart => text.push_str(&format!("{frame} restart\n")),
+                Input::Undo => text.push_str(&format!("{frame} undo\n")),
+                Input::Hold(direction) => {
+                    let direction = match direction {
+                        Direction::Left => "left",
+                        Direction::Right => "right",
+                        Direction::Up => "up",
+                        Direction::Down => "down",
+                    };
+                    text.push_str(&format!("{frame} {direction} {count}\n"));
+                }
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Replay, String> {
+        Replay::parse(Path::new("replay.txt"), content)
+    }
+
+    #[test]
+    fn inputs_are_kept_as_runs() {
+        let mut replay = Replay::new("Normal-1", None);
+        for frame in 3..6 {
+            replay.record(frame, Input::Hold(Direction::Right));
+        }
+        replay.record(6, Input::Undo);
+        replay.record(6, Input::Hold(Direction::Right));
+        replay.record(7, Input::Hold(Direction::Right));
+        assert_eq!(
+            replay.inputs,
+            [
+                (3, Input::Hold(Direction::Right), 3),
+                (6, Input::Undo, 1),
+                (6, Input::Hold(Direction::Right), 2),
+            ]
+        );
+        assert_eq!(replay.inputs_at(2).count(), 0);
+        assert_eq!(
+            replay.inputs_at(4).collect::<Vec<_>>(),
+            [Input::Hold(Direction::Right)]
+        );
+        assert_eq!(
+            replay.inputs_at(6).collect::<Vec<_>>(),
+            [Input::Undo, Input::Hold(Direction::Right)]
+        );
+        assert_eq!(replay.end_frame(), 8);
+    }
+
+    #[test]
+    fn a_run_past_the_last_frame_is_an_error() {
+        let replay = parse("version = 0.1.0\nlevel = Normal-1\n4294967290 right 10\n");
+        assert_eq!(
+            replay.unwrap_err(),
+            "replay.txt:3: Frame 4294967290 held for 10 frames is too late"
+        );
+    }
+
+    #[test]
+    fn a_malformed_line_is_an_error() {
+        let err = parse("version = 0.1.0\nlevel = Normal-1\n12 left\n").unwrap_err();
+        assert!(
+            err.starts_with("replay.txt:3: Expected `<frame> <direction>"),
+            "{err}"
+        );
+        let err = parse("version = 0.1.0\nlevel = Normal-1\n12 sideways 2\n").unwrap_err();
+        assert_eq!(err, "replay.txt:3: Invalid direction: sideways");
+        let err = parse("version = 0.1.0\n").unwrap_err();
+        assert_eq!(err, "replay.txt: Missing level");
+    }
+
+    #[test]
+    fn an_unknown_key_is_an_error() {
+        assert_eq!(
+            parse("version = 0.1.0\nspeed = 2\nlevel = Normal-1\n").unwrap_err(),
+            "replay.txt:2: Unknown key: speed"
+        );
+    }
+
+    #[test]
+    fn saved_replays_load_back() {
+        let mut replay = Replay::new("levels/first.blm", Some(Path::new("levels/pack.txt")));
+        replay.record(2, Input::Hold(Direction::Up));
+        replay.record(3, Input::Hold(Direction::Up));
+        replay.record(9, Input::Restart);
+        replay.record(12, Input::Hold(Direction::Left));
+        let path = std::env::temp_dir().join(format!("bobby-saved-{}.txt", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.pack, replay.pack);
+        assert_eq!(loaded.level, replay.level);
+        assert_eq!(loaded.inputs, replay.inputs);
+    }
+}
@@ -0,0 +1,116 @@
+//! The options of the game other than the keys.
+//!
+//! The settings file is a text file of `name = value` lines, see
+//! [`bobby_carrot::key_value`]. The settings left out keep their default
+//! value:
+//!
+//! ```text
+//! # in points, a tile is 32
+//! camera_dead_zone = 16
+//! camera_look_ahead = 32
+//! # in seconds, 0 for a camera that doesn't ease
+//! camera_easing = 0.15
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use bobby_carrot::{config_dir, key_value};
+
+use crate::camera::CameraSettings;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Settings {
+    pub camera: CameraSettings,
+}
+
+impl Settings {
+    /// `settings.txt` in the [`config_dir`].
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("settings.txt"))
+    }
+
+    /// Read a settings file, a missing one leaves the defaults.
+    pub fn load(path: &Path) -> Result<Settings, Box<dyn std::error::Error>> {
+        match key_value::read_if_exists(path)? {
+            Some(content) => Ok(Settings::parse(path, &content)?),
+            None => Ok(Settings::default()),
+        }
+    }
+
+    /// Parse `content`, the text of the settings file at `path`.
+    fn parse(path: &Path, content: &str) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+        for line in key_value::lines(path, content) {
+            let (name, value) = line.key_value()?;
+            let invalid = || line.error(format!("Invalid {name}: {value}"));
+            match name {
+                "camera_dead_zone" => {
+                    settings.camera.dead_zone = value.parse().map_err(|_| invalid())?
+                }
+                "camera_look_ahead" => {
+                    settings.camera.look_ahead = value.parse().map_err(|_| invalid())?
+                }
+                "camera_easing" => {
+                    let easing: f32 = value.parse().map_err(|_| invalid())?;
+                    if !easing.is_finite() || easing < 0.0 {
+                        return Err(invalid());
+                    }
+                    settings.camera.easing = easing;
+                }
+                _ => return Err(line.error(format!("Unknown setting: {name}"))),
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(key_value::write(path, &self.to_text())?)
+    }
+
+    /// The text of the settings file.
+    fn to_text(self) -> String {
+        let camera = &self.camera;
+        format!(
+            "camera_dead_zone = {}\ncamera_look_ahead = {}\ncamera_easing = {}\n",
+            camera.dead_zone, camera.look_ahead, camera.easing
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Settings, String> {
+        Settings::parse(Path::new("settings.txt"), content)
+    }
+
+    #[test]
+    fn a_malformed_line_is_an_error() {
+        let err = parse("camera_dead_zone 16\n").unwrap_err();
+        assert!(
+            err.starts_with("settings.txt:1: Expected `key = value`"),
+            "{err}"
+        );
+        assert_eq!(
+            parse("# easing\ncamera_easing = -1\n").unwrap_err(),
+            "settings.txt:2: Invalid camera_easing: -1"
+        );
+    }
+
+    #[test]
+    fn an_unknown_setting_is_an_error() {
+        assert_eq!(
+            parse("camera_zoom = 2\n").unwrap_err(),
+            "settings.txt:1: Unknown setting: camera_zoom"
+        );
+    }
+
+    #[test]
+    fn saved_settings_load_back() {
+        let mut settings = Settings::default();
+        settings.camera.dead_zone = 8;
+        settings.camera.easing = 0.5;
+        assert_eq!(parse(&settings.to_text()), Ok(settings));
+    }
+}
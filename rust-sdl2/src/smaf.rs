@@ -0,0 +1,154 @@
+//! The sound effects of the original game are SMAF (`.mmf`) files, that
+//! SDL_mixer can't read: they hold a 4-bit Yamaha ADPCM sample, decoded here
+//! into a plain WAV.
+
+/// A mono 16-bit sample.
+#[derive(Debug, Clone)]
+pub struct Wave {
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+/// Decode the first wave of the audio track of a SMAF file.
+pub fn decode_wave(data: &[u8]) -> Result<Wave, Box<dyn std::error::Error>> {
+    if !data.starts_with(b"MMMD") {
+        return Err("Not a SMAF file".into());
+    }
+    let (_, body) = chunk(data, 0).ok_or("Truncated SMAF file")?;
+    let mut offset = 0;
+    let track = loop {
+        let (id, content) = chunk(body, offset).ok_or("No audio track in SMAF file")?;
+        offset += 8 + content.len();
+        if id.starts_with(b"ATR") {
+            break content;
+        }
+    };
+    // format, sequence, wave type, table size, time bases
+    let [_, _, wave_type, ..] = track[..] else {
+        return Err("Invalid audio track".into());
+    };
+    if wave_type & 0x80 != 0 || (wave_type >> 4) & 0x7 != 1 {
+        return Err(format!("Unsupported wave type {wave_type:#04x}, only mono ADPCM is").into());
+    }
+    let sample_rate = match wave_type & 0xf {
+        0 => 4000,
+        1 => 8000,
+        2 => 11025,
+        3 => 22050,
+        4 => 44100,
+        rate => return Err(format!("Unsupported sample rate {rate}").into()),
+    };
+
+    let mut offset = 6;
+    let wave = loop {
+        let (id, content) = chunk(track, offset).ok_or("No wave in SMAF file")?;
+        offset += 8 + content.len();
+        if id.starts_with(b"Awa") {
+            break content;
+        }
+    };
+    Ok(Wave {
+        sample_rate,
+        samples: decode_adpcm(wave),
+    })
+}
+
+/// The id and content of the chunk at `offset`, if it is complete.
+fn chunk(data: &[u8], offset: usize) -> Option<(&[u8], &[u8])> {
+    let header = data.get(offset..offset + 8)?;
+    let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let content = data.get(offset + 8..offset + 8 + size)?;
+    Some((&header[..4], content))
+}
+
+/// Yamaha ADPCM, the low nibble of each byte first.
+fn decode_adpcm(data: &[u8]) -> Vec<i16> {
+    const DIFF: [i32; 16] = [1, 3, 5, 7, 9, 11, 13, 15, -1, -3, -5, -7, -9, -11, -13, -15];
+    const SCALE: [i32; 16] = [
+        230, 230, 230, 230, 307, 409, 512, 614, 230, 230, 230, 230, 307, 409, 512, 614,
+    ];
+    let mut predictor = 0i32;
+    let mut step = 127i32;
+    let mut samples = Vec::with_capacity(data.len() * 2);
+    for byte in data {
+        for nibble in [byte & 0xf, byte >> 4] {
+            let nibble = nibble as usize;
+            predictor =
+                (predictor + step * DIFF[nibble] / 8).clamp(i16::MIN as i32, i16::MAX as i32);
+            step = ((step * SCALE[nibble]) >> 8).clamp(127, 24576);
+            samples.push(predictor as i16);
+        }
+    }
+    samples
+}
+
+impl Wave {
+    /// The sample as a `.wav` file.
+    pub fn to_wav(&self) -> Vec<u8> {
+        let data_size = (self.samples.len() * 2) as u32;
+        let mut wav = Vec::with_capacity(44 + data_size as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, mono
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&self.sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(self.sample_rate * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        for sample in &self.samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn decodes_the_carrot_sound() {
+        let wave = decode_wave(include_bytes!("assets/audio/carrot.mmf")).unwrap();
+        assert_eq!(wave.sample_rate, 8000);
+        assert_eq!(wave.samples.len(), 2694);
+        assert!(wave.samples.iter().any(|sample| *sample != 0));
+
+        let wav = wave.to_wav();
+        let data_size = 2 * 2694;
+        assert_eq!(wav.len(), 44 + data_size);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        // PCM, mono, 16 bits
+        assert_eq!(&wav[20..24], [1, 0, 1, 0]);
+        assert_eq!(u32_at(&wav, 24), 8000);
+        assert_eq!(u32_at(&wav, 28), 16000);
+        assert_eq!(&wav[32..36], [2, 0, 16, 0]);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40) as usize, data_size);
+        assert_eq!(&wav[44..46], wave.samples[0].to_le_bytes());
+    }
+
+    #[test]
+    fn music_without_an_audio_track_is_an_error() {
+        let err = decode_wave(include_bytes!("assets/audio/title.mmf")).unwrap_err();
+        assert_eq!(err.to_string(), "No audio track in SMAF file");
+    }
+
+    #[test]
+    fn truncated_files_are_an_error() {
+        let data = include_bytes!("assets/audio/carrot.mmf");
+        for len in [0, 4, 8, 20, 40, data.len() / 2] {
+            assert!(decode_wave(&data[..len]).is_err(), "{len} bytes");
+        }
+    }
+}
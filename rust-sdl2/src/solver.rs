@@ -0,0 +1,973 @@
+//! A* search over the full game state.
+//!
+//! Moves are played through [`GameState::step`], so the solver follows
+//! exactly the rules of the game: a move is one direction pressed while
+//! Bobby stands still, followed by everything it causes (conveyors pushing
+//! him further, switches, crumbling tiles, ...) until he stands still again.
+//! The cost of a move is the number of tiles Bobby walked.
+//!
+//! The estimate of the steps left never exceeds the steps Bobby still has to
+//! walk, so the first moves reaching the finish are the shortest ones.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::game::{Direction, GameEvent, GameState};
+use crate::grid::Grid;
+use crate::map::Goal;
+use crate::tile::{Collectible, Tile};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Right,
+    Direction::Up,
+    Direction::Down,
+];
+
+/// A move never takes longer than walking over every tile a few times, if it
+/// does Bobby is stuck in a loop of conveyors.
+const MAX_FRAMES_PER_TILE: u32 = 16 * 4;
+
+/// Index in the search history of the state the search started from.
+const NO_PARENT: usize = usize::MAX;
+
+/// No tile, in [`Dominators`].
+const NONE: usize = usize::MAX;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Solution {
+    /// The moves reaching the finish.
+    Solved(Vec<Direction>),
+    /// Every reachable state was explored without reaching the finish.
+    Unsolvable { states: usize },
+}
+
+/// Everything that matters for the rules, positions in an animation and
+/// timers aside: Bobby's tile, and which of its states each tile that can
+/// change is in. The counters of Bobby follow from the tiles, see
+/// [`Layout::rebuild`].
+#[derive(Clone, Hash, Eq, PartialEq)]
+struct Key {
+    position: u32,
+    /// One or two bits per tile, see [`Layout`].
+    bits: Box<[u64]>,
+}
+
+/// How the tiles that walking around can change are packed into a [`Key`].
+struct Layout {
+    /// The state the search started from, Bobby standing still.
+    start: GameState,
+    tiles: Vec<Changing>,
+    words: usize,
+}
+
+/// A tile that walking around can change.
+struct Changing {
+    idx: usize,
+    /// The tiles it can turn into, the first one is how it starts.
+    states: Vec<Tile>,
+    /// Where its bits are in [`Key::bits`].
+    offset: usize,
+    /// Whether Bobby's counters follow from its state: a collectible or a
+    /// lock, which is kept in the key even out of Bobby's reach.
+    counted: bool,
+}
+
+impl Layout {
+    fn new(start: &GameState) -> Layout {
+        let mut tiles = start
+            .map_info()
+            .data
+            .tiles()
+            .iter()
+            .enumerate()
+            .map(|(idx, tile)| Changing {
+                idx,
+                states: tile_states(*tile),
+                offset: 0,
+                counted: tile.collectible().is_some() || tile.lock().is_some(),
+            })
+            .filter(|changing| changing.states.len() > 1)
+            .collect::<Vec<_>>();
+        // The tiles of two bits go first, so that none of them straddles
+        // two words
+        tiles.sort_by_key(|changing| (changing.states.len() <= 2, changing.idx));
+        let mut offset = 0;
+        for changing in &mut tiles {
+            changing.offset = offset;
+            offset += if changing.states.len() > 2 { 2 } else { 1 };
+        }
+        Layout {
+            start: start.clone(),
+            tiles,
+            words: offset.div_ceil(64),
+        }
+    }
+
+    /// The key of `game`, given the distances from Bobby of [`Walks`].
+    ///
+    /// Bobby never gets next to the tiles out of his reach again, so how
+    /// they are doesn't matter and they are left as they started: the
+    /// states differing only there are the same for the search. Tiles next
+    /// to his reach are kept, they could be holes he walks into.
+    fn key(&self, game: &GameState, reach: &[u32]) -> Key {
+        let grid = &game.map_info().data;
+        let data = grid.tiles();
+        let mut bits = vec![0; self.words].into_boxed_slice();
+        for changing in &self.tiles {
+            let Changing {
+                idx,
+                states,
+                offset,
+                counted,
+            } = changing;
+            if !counted
+                && reach[*idx] == u32::MAX
+                && neighbours(grid, *idx).all(|(_, next)| reach[next] == u32::MAX)
+            {
+                continue;
+            }
+            let state = states
+                .iter()
+                .position(|tile| *tile == data[*idx])
+                .expect("tiles only change into one of their states");
+            bits[offset / 64] |= (state as u64) << (offset % 64);
+        }
+        Key {
+            position: grid.index(game.bobby().coord_src) as u32,
+            bits,
+        }
+    }
+
+    /// The game in the state of `key`, Bobby standing still. What Bobby
+    /// collected and the locks he opened since the start give his counters.
+    fn rebuild(&self, key: &Key) -> GameState {
+        let mut snapshot = self.start.snapshot();
+        let bobby = &mut snapshot.bobby;
+        let tiles = snapshot.data.tiles_mut();
+        for Changing {
+            idx,
+            states,
+            offset,
+            ..
+        } in &self.tiles
+        {
+            let mask = if states.len() > 2 { 0b11 } else { 0b1 };
+            let state = (key.bits[offset / 64] >> (offset % 64)) & mask;
+            let tile = states[state as usize];
+            if tile != tiles[*idx] {
+                match tiles[*idx].collectible() {
+                    Some(Collectible::Carrot) => bobby.carrot_count += 1,
+                    Some(Collectible::Egg) => bobby.egg_count += 1,
+                    Some(Collectible::Key(color)) => *bobby.key_mut(color) += 1,
+                    None => {}
+                }
+                if let Some(color) = tiles[*idx].lock() {
+                    *bobby.key_mut(color) -= 1;
+                }
+            }
+            tiles[*idx] = tile;
+        }
+        bobby.coord_src = snapshot.data.coord(key.position as usize);
+        let mut game = self.start.clone();
+        game.restore(&snapshot);
+        game
+    }
+}
+
+/// The tiles `tile` can turn into, itself first.
+fn tile_states(tile: Tile) -> Vec<Tile> {
+    let mut states = vec![tile];
+    let mut idx = 0;
+    while let Some(&tile) = states.get(idx) {
+        let opened = if tile.lock().is_some() {
+            Tile::Floor
+        } else if tile == Tile::Crumble {
+            Tile::Hole
+        } else {
+            tile
+        };
+        for next in [tile.toggled(), tile.collected(), opened] {
+            if !states.contains(&next) {
+                states.push(next);
+            }
+        }
+        idx += 1;
+    }
+    states
+}
+
+enum Outcome {
+    Alive,
+    Finished,
+    Dead,
+}
+
+struct Node {
+    parent: usize,
+    direction: Direction,
+    /// Taken out once the node is expanded, there is none once finished.
+    key: Option<Key>,
+}
+
+/// Solve the level from its current state.
+pub fn solve(game: &GameState) -> Solution {
+    solve_with_limit(game, usize::MAX).expect("no limit on the number of states")
+}
+
+/// Like [`solve`], but gives up with `None` after exploring `max_states`.
+pub fn solve_with_limit(game: &GameState, max_states: usize) -> Option<Solution> {
+    solve_cancellable(game, max_states, &AtomicBool::new(false))
+}
+
+/// Like [`solve_with_limit`], but also gives up as soon as `cancel` is set,
+/// from another thread once the answer isn't wanted anymore.
+pub fn solve_cancellable(
+    game: &GameState,
+    max_states: usize,
+    cancel: &AtomicBool,
+) -> Option<Solution> {
+    let mut start = game.clone();
+    match settle(&mut start, Vec::new()).0 {
+        Outcome::Alive => {}
+        Outcome::Finished => return Some(Solution::Solved(Vec::new())),
+        Outcome::Dead => return Some(Solution::Unsolvable { states: 0 }),
+    }
+    let mut heuristic = Heuristic::new(&start);
+    let layout = Layout::new(&start);
+    let start_key = layout.key(&start, heuristic.reach(&start));
+    let Some(estimate) = heuristic.estimate(&start) else {
+        return Some(Solution::Unsolvable { states: 0 });
+    };
+
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut best = HashMap::new();
+    let mut heap = BinaryHeap::new();
+    // Among equal estimates the deepest node goes first, it is the closest to
+    // the finish
+    heap.push((Reverse(estimate), 0, NO_PARENT));
+    let mut start_key = Some(start_key);
+    while let Some((_, cost, index)) = heap.pop() {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        let key = if index == NO_PARENT {
+            start_key.take()
+        } else {
+            nodes[index].key.take()
+        };
+        let Some(key) = key else {
+            return Some(Solution::Solved(moves_to(&nodes, index)));
+        };
+        // An outdated entry, the state was reached again with a lower cost
+        if best.get(&key).is_some_and(|&c| c < cost) {
+            continue;
+        }
+        let game = layout.rebuild(&key);
+        for direction in DIRECTIONS {
+            let mut next = game.clone();
+            let events = next.step(Some(direction));
+            let (outcome, steps) = settle(&mut next, events);
+            if steps == 0 || matches!(outcome, Outcome::Dead) {
+                continue;
+            }
+            let next_cost = cost + steps;
+            let (key, estimate) = if matches!(outcome, Outcome::Finished) {
+                (None, 0)
+            } else {
+                let key = layout.key(&next, heuristic.reach(&next));
+                if best.get(&key).is_some_and(|&c| c <= next_cost) {
+                    continue;
+                }
+                if best.len() >= max_states {
+                    return None;
+                }
+                best.insert(key.clone(), next_cost);
+                // Bobby can't finish from there anymore
+                let Some(estimate) = heuristic.estimate(&next) else {
+                    continue;
+                };
+                (Some(key), estimate)
+            };
+            nodes.push(Node {
+                parent: index,
+                direction,
+                key,
+            });
+            let priority = next_cost + estimate;
+            heap.push((Reverse(priority), next_cost, nodes.len() - 1));
+        }
+    }
+    Some(Solution::Unsolvable { states: best.len() })
+}
+
+/// An estimate of the steps left to finish the level.
+struct Heuristic {
+    walks: Walks,
+    dominators: Dominators,
+    /// The carrots (or eggs) at the start, by index of their tile.
+    targets: Vec<usize>,
+    target: Tile,
+    /// Shortest walks between each two targets, one way or the other, as
+    /// the level was at the start.
+    between: Vec<Vec<u32>>,
+    /// Shortest walks from each target to the nearest finish.
+    to_finish: Vec<u32>,
+    finishes: Vec<usize>,
+    /// Distances from Bobby, reused from one estimate to the next.
+    from_bobby: Vec<u32>,
+    queue: VecDeque<usize>,
+}
+
+impl Heuristic {
+    /// The distances from Bobby to every tile, `u32::MAX` out of his reach.
+    fn reach(&mut self, game: &GameState) -> &[u32] {
+        let data = &game.map_info().data;
+        let position = data.index(game.bobby().coord_src);
+        self.walks.link(data);
+        self.walks
+            .distances(position, &mut self.from_bobby, &mut self.queue);
+        &self.from_bobby
+    }
+
+    fn new(game: &GameState) -> Heuristic {
+        let map_info = game.map_info();
+        let data = &map_info.data;
+        let mut walks = Walks::new(data);
+        walks.link(data);
+        let target = match map_info.goal {
+            Goal::Carrots => Tile::Carrot,
+            Goal::Eggs => Tile::Egg,
+        };
+        let find = |wanted: &dyn Fn(Tile) -> bool| {
+            data.tiles()
+                .iter()
+                .enumerate()
+                .filter(|(_, tile)| wanted(**tile))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>()
+        };
+        let targets = find(&|tile| tile == target);
+        let finishes = find(&|tile| tile == Tile::Finish);
+        let mut distances = vec![u32::MAX; data.tiles().len()];
+        let mut queue = VecDeque::new();
+        let mut one_way = Vec::new();
+        let mut to_finish = Vec::new();
+        for from in &targets {
+            walks.distances(*from, &mut distances, &mut queue);
+            one_way.push(targets.iter().map(|to| distances[*to]).collect::<Vec<_>>());
+            to_finish.push(
+                finishes
+                    .iter()
+                    .map(|finish| distances[*finish])
+                    .min()
+                    .unwrap_or(u32::MAX),
+            );
+        }
+        let between = (0..targets.len())
+            .map(|i| {
+                (0..targets.len())
+                    .map(|j| one_way[i][j].min(one_way[j][i]))
+                    .collect()
+            })
+            .collect();
+        Heuristic {
+            dominators: Dominators::new(data.tiles().len()),
+            walks,
+            targets,
+            target,
+            between,
+            to_finish,
+            finishes,
+            from_bobby: distances,
+            queue,
+        }
+    }
+
+    /// Bobby has to walk a path through all the carrots (or eggs) left, then
+    /// to the finish: that path is at least as long as the minimum spanning
+    /// tree of those tiles. Every step also collects at most one of them.
+    /// The estimate is never more than the steps left, so that the search
+    /// finds the shortest moves.
+    ///
+    /// `None` if one of the carrots (or eggs), or the finish, can't be
+    /// reached anymore.
+    ///
+    /// The distances from Bobby are those of the last [`Heuristic::reach`],
+    /// on the same `game`.
+    fn estimate(&mut self, game: &GameState) -> Option<u32> {
+        let map_info = game.map_info();
+        let data = &map_info.data;
+        let position = data.index(game.bobby().coord_src);
+        let to_finish = self
+            .finishes
+            .iter()
+            .map(|finish| self.from_bobby[*finish])
+            .min()
+            .filter(|distance| *distance != u32::MAX)?;
+        let tiles = data.tiles();
+        let mut left = (0..self.targets.len())
+            .filter(|i| tiles[self.targets[*i]] == self.target)
+            .collect::<Vec<_>>();
+        if left.is_empty() {
+            return Some(to_finish);
+        }
+        if left
+            .iter()
+            .any(|i| self.from_bobby[self.targets[*i]] == u32::MAX)
+            || self
+                .dominators
+                .is_stranded(&self.walks, data, position, &self.finishes, self.target)
+        {
+            return None;
+        }
+        let last_leg = left.iter().map(|i| self.to_finish[*i]).min().unwrap_or(0);
+        let count = left.len() as u32;
+        let mut nearest = left
+            .iter()
+            .map(|i| self.from_bobby[self.targets[*i]])
+            .collect::<Vec<_>>();
+        // the last carrot is never on the finish tile, while the last egg is
+        // counted when stepping from it onto the finish
+        let count = match map_info.goal {
+            Goal::Carrots => count + 1,
+            Goal::Eggs => count,
+        };
+
+        // Prim's algorithm, starting from Bobby
+        let mut tree = 0u32;
+        while !left.is_empty() {
+            let (i, distance) = nearest
+                .iter()
+                .copied()
+                .enumerate()
+                .min_by_key(|(_, distance)| *distance)
+                .expect("targets left");
+            tree = tree.saturating_add(distance);
+            let added = left.swap_remove(i);
+            nearest.swap_remove(i);
+            for (j, distance) in left.iter().zip(nearest.iter_mut()) {
+                *distance = (*distance).min(self.between[added][*j]);
+            }
+        }
+        Some(tree.saturating_add(last_leg).max(count))
+    }
+}
+
+/// The walks that may still be possible, whatever the switches turn the
+/// rails and conveyors into: a rail can be passed in every direction, a
+/// conveyor only along its line. Walls, holes and placed eggs are never
+/// walked on again.
+struct Walks {
+    /// By [`Tile::to_byte`], the directions Bobby may walk onto the tile
+    /// in, and off it in, one bit per direction of [`DIRECTIONS`].
+    sides: Vec<(u8, u8)>,
+    /// By tile of the level, the directions Bobby may walk off it onto its
+    /// neighbour in, as of the last [`Walks::link`].
+    links: Vec<u8>,
+    /// By tile, the directions of the neighbours Bobby may walk from onto it.
+    back_links: Vec<u8>,
+    /// By tile, its [`Walks::sides`] as of the last [`Walks::link`].
+    linked: Vec<(u8, u8)>,
+    /// Counts the times the links changed.
+    version: u64,
+    width: usize,
+}
+
+impl Walks {
+    fn new(grid: &Grid) -> Walks {
+        let len = grid.tiles().len();
+        let sides = (0..=u8::MAX)
+            .map_while(Tile::from_byte)
+            .map(|tile| {
+                let states = tile_states(tile);
+                let mut enter = 0;
+                let mut leave = 0;
+                for (bit, direction) in DIRECTIONS.into_iter().enumerate() {
+                    if tile != Tile::Hole && states.iter().any(|tile| tile.can_enter(direction)) {
+                        enter |= 1 << bit;
+                    }
+                    if states.iter().any(|tile| tile.can_leave(direction)) {
+                        leave |= 1 << bit;
+                    }
+                }
+                (enter, leave)
+            })
+            .collect();
+        Walks {
+            sides,
+            links: vec![0; len],
+            back_links: vec![0; len],
+            linked: Vec::new(),
+            version: 0,
+            width: grid.width() as usize,
+        }
+    }
+
+    /// Find the walks between each two tiles of `grid`, for the searches
+    /// that follow. Nothing is done if its tiles are walked on as in the
+    /// last `grid` linked.
+    fn link(&mut self, grid: &Grid) {
+        let tiles = grid.tiles();
+        if self.linked.len() == tiles.len()
+            && tiles
+                .iter()
+                .zip(&self.linked)
+                .all(|(tile, linked)| self.sides[tile.to_byte() as usize] == *linked)
+        {
+            return;
+        }
+        self.linked.clear();
+        self.linked
+            .extend(tiles.iter().map(|tile| self.sides[tile.to_byte() as usize]));
+        self.version += 1;
+        let sides = &self.linked;
+        let width = self.width;
+        for (idx, links) in self.links.iter_mut().enumerate() {
+            let enter = |next: usize, bit: usize| sides[next].0 & (1 << bit);
+            let mut enterable = 0;
+            if idx % width > 0 {
+                enterable |= enter(idx - 1, 0);
+            }
+            if idx % width + 1 < width {
+                enterable |= enter(idx + 1, 1);
+            }
+            if idx >= width {
+                enterable |= enter(idx - width, 2);
+            }
+            if idx + width < sides.len() {
+                enterable |= enter(idx + width, 3);
+            }
+            *links = enterable & sides[idx].1;
+        }
+        self.back_links.fill(0);
+        for idx in 0..self.links.len() {
+            for bit in 0..DIRECTIONS.len() {
+                if let Some(next) = self.step(idx, bit) {
+                    // the directions of DIRECTIONS come in opposite pairs
+                    self.back_links[next] |= 1 << (bit ^ 1);
+                }
+            }
+        }
+    }
+
+    /// The tile Bobby may walk onto from the one at `idx`, in the direction
+    /// of `bit`.
+    fn step(&self, idx: usize, bit: usize) -> Option<usize> {
+        if self.links[idx] & (1 << bit) == 0 {
+            return None;
+        }
+        Some(self.offset(idx, bit))
+    }
+
+    /// The tile next to the one at `idx`, in the direction of `bit`, which
+    /// must be inside the level.
+    fn offset(&self, idx: usize, bit: usize) -> usize {
+        match bit {
+            0 => idx - 1,
+            1 => idx + 1,
+            2 => idx - self.width,
+            _ => idx + self.width,
+        }
+    }
+
+    /// Like [`Walks::step`], or `back` the tile Bobby may walk from onto the
+    /// one at `idx`.
+    fn walk(&self, idx: usize, bit: usize, back: bool) -> Option<usize> {
+        if !back {
+            return self.step(idx, bit);
+        }
+        if self.back_links[idx] & (1 << bit) == 0 {
+            return None;
+        }
+        Some(self.offset(idx, bit))
+    }
+
+    /// The tiles Bobby may walk onto from the one at `idx`.
+    fn next(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..DIRECTIONS.len()).filter_map(move |bit| self.step(idx, bit))
+    }
+
+    /// The shortest walks from `from` to every tile.
+    fn distances(&self, from: usize, distances: &mut [u32], queue: &mut VecDeque<usize>) {
+        distances.fill(u32::MAX);
+        distances[from] = 0;
+        queue.push_back(from);
+        while let Some(idx) = queue.pop_front() {
+            for next in self.next(idx) {
+                if distances[next] == u32::MAX {
+                    distances[next] = distances[idx] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+}
+
+/// The tiles every walk to a tile passes (its dominators), to find the tiles
+/// Bobby would need to cross twice.
+struct Dominators {
+    /// The immediate dominators of the tiles, walking from Bobby and walking
+    /// back from the finishes, see [`Dominators::find`].
+    from_bobby: Vec<usize>,
+    to_finish: Vec<usize>,
+    /// The [`Walks::version`] of `to_finish`, which doesn't depend on where
+    /// Bobby is.
+    to_finish_version: Option<u64>,
+    /// The targets that can't reach the finish, or only past a tile that
+    /// blocks once walked over, as of `to_finish`: only they can strand
+    /// Bobby.
+    guarded: Vec<usize>,
+    /// By tile, the last check of a target that found it dominating.
+    marks: Vec<u64>,
+    checks: u64,
+    // Lengauer and Tarjan, by tile and the root past the end: the order of
+    // a depth-first search, the tiles in that order, their parents in the
+    // search, semidominators, forest of the processed tiles and buckets
+    order: Vec<u32>,
+    tiles: Vec<usize>,
+    parent: Vec<usize>,
+    semi: Vec<u32>,
+    ancestor: Vec<usize>,
+    label: Vec<usize>,
+    bucket: Vec<usize>,
+    next_in_bucket: Vec<usize>,
+    stack: Vec<(usize, usize)>,
+    path: Vec<usize>,
+}
+
+impl Dominators {
+    fn new(len: usize) -> Dominators {
+        Dominators {
+            from_bobby: Vec::new(),
+            to_finish: Vec::new(),
+            to_finish_version: None,
+            guarded: Vec::new(),
+            marks: vec![0; len],
+            checks: 0,
+            order: vec![0; len + 1],
+            tiles: Vec::new(),
+            parent: vec![NONE; len + 1],
+            semi: vec![0; len + 1],
+            ancestor: vec![NONE; len + 1],
+            label: vec![0; len + 1],
+            bucket: vec![NONE; len + 1],
+            next_in_bucket: vec![NONE; len + 1],
+            stack: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    /// Bobby can't walk over all the `target` tiles left and then onto one
+    /// of the `finishes` anymore, because of a tile that blocks once walked
+    /// over (an egg or a crumbling floor) that he would need to cross twice:
+    /// every walk from him to a target and every walk from it to the finish
+    /// pass that tile.
+    fn is_stranded(
+        &mut self,
+        walks: &Walks,
+        grid: &Grid,
+        from: usize,
+        finishes: &[usize],
+        target: Tile,
+    ) -> bool {
+        let tiles = grid.tiles();
+        let once = |idx: usize| matches!(tiles[idx], Tile::Egg | Tile::Crumble);
+        let root = tiles.len();
+        let mut from_bobby = std::mem::take(&mut self.from_bobby);
+        let mut to_finish = std::mem::take(&mut self.to_finish);
+        if self.to_finish_version != Some(walks.version) {
+            self.find(walks, finishes, true, &mut to_finish);
+            self.to_finish_version = Some(walks.version);
+            self.guarded.clear();
+            self.guarded.extend((0..root).filter(|idx| {
+                if tiles[*idx] != target {
+                    return false;
+                }
+                let mut dominator = to_finish[*idx];
+                while dominator != root {
+                    if dominator == NONE || once(dominator) {
+                        return true;
+                    }
+                    dominator = to_finish[dominator];
+                }
+                false
+            }));
+        }
+        let mut guarded = std::mem::take(&mut self.guarded);
+        guarded.retain(|idx| tiles[*idx] == target);
+        if !guarded.is_empty() {
+            self.find(walks, &[from], false, &mut from_bobby);
+        }
+        let stranded = guarded.iter().any(|&idx| {
+            if from_bobby[idx] == NONE {
+                return false;
+            }
+            if to_finish[idx] == NONE {
+                return true;
+            }
+            self.checks += 1;
+            let mut dominator = from_bobby[idx];
+            while dominator != root {
+                self.marks[dominator] = self.checks;
+                dominator = from_bobby[dominator];
+            }
+            let mut dominator = to_finish[idx];
+            while dominator != root {
+                if self.marks[dominator] == self.checks && once(dominator) {
+                    return true;
+                }
+                dominator = to_finish[dominator];
+            }
+            false
+        });
+        self.from_bobby = from_bobby;
+        self.to_finish = to_finish;
+        self.guarded = guarded;
+        stranded
+    }
+
+    /// The immediate dominator of each tile Bobby may walk to from one of
+    /// the `roots`, or `back` from each tile he may walk from to one of them:
+    /// the tile closest to it that all those walks pass. The roots are
+    /// dominated by the tile past the end of the level, and the tiles out of
+    /// reach by [`NONE`]. This is the simple algorithm of Lengauer and Tarjan.
+    fn find(&mut self, walks: &Walks, roots: &[usize], back: bool, dominators: &mut Vec<usize>) {
+        let root = self.order.len() - 1;
+        self.order.fill(u32::MAX);
+        self.tiles.clear();
+        self.order[root] = 0;
+        self.tiles.push(root);
+        for &start in roots {
+            if self.order[start] != u32::MAX {
+                continue;
+            }
+            self.order[start] = self.tiles.len() as u32;
+            self.tiles.push(start);
+            self.parent[start] = root;
+            self.stack.push((start, 0));
+            while let Some((idx, bit)) = self.stack.pop() {
+                if bit == DIRECTIONS.len() {
+                    continue;
+                }
+                self.stack.push((idx, bit + 1));
+                if let Some(next) = walks.walk(idx, bit, back) {
+                    if self.order[next] == u32::MAX {
+                        self.order[next] = self.tiles.len() as u32;
+                        self.tiles.push(next);
+                        self.parent[next] = idx;
+                        self.stack.push((next, 0));
+                    }
+                }
+            }
+        }
+        for &idx in &self.tiles {
+            self.semi[idx] = self.order[idx];
+            self.ancestor[idx] = NONE;
+            self.label[idx] = idx;
+            self.bucket[idx] = NONE;
+        }
+        dominators.clear();
+        dominators.resize(root + 1, NONE);
+        for i in (1..self.tiles.len()).rev() {
+            let idx = self.tiles[i];
+            let parent = self.parent[idx];
+            if parent == root {
+                self.semi[idx] = 0;
+            } else {
+                for bit in 0..DIRECTIONS.len() {
+                    let Some(previous) = walks.walk(idx, bit, !back) else {
+                        continue;
+                    };
+                    if self.order[previous] == u32::MAX {
+                        continue;
+                    }
+                    let lowest = self.eval(previous);
+                    self.semi[idx] = self.semi[idx].min(self.semi[lowest]);
+                }
+            }
+            let semi = self.tiles[self.semi[idx] as usize];
+            self.next_in_bucket[idx] = self.bucket[semi];
+            self.bucket[semi] = idx;
+            self.ancestor[idx] = parent;
+            let mut next = std::mem::replace(&mut self.bucket[parent], NONE);
+            while next != NONE {
+                let lowest = self.eval(next);
+                dominators[next] = if self.semi[lowest] < self.semi[next] {
+                    lowest
+                } else {
+                    parent
+                };
+                next = self.next_in_bucket[next];
+            }
+        }
+        dominators[root] = root;
+        for &idx in &self.tiles[1..] {
+            if dominators[idx] != self.tiles[self.semi[idx] as usize] {
+                dominators[idx] = dominators[dominators[idx]];
+            }
+        }
+    }
+
+    /// The tile of lowest semidominator on the way up the forest from the
+    /// one at `idx`, compressing that way.
+    fn eval(&mut self, idx: usize) -> usize {
+        if self.ancestor[idx] == NONE {
+            return idx;
+        }
+        let mut path = std::mem::take(&mut self.path);
+        let mut above = idx;
+        while self.ancestor[self.ancestor[above]] != NONE {
+            path.push(above);
+            above = self.ancestor[above];
+        }
+        for &below in path.iter().rev() {
+            let ancestor = self.ancestor[below];
+            if self.semi[self.label[ancestor]] < self.semi[self.label[below]] {
+                self.label[below] = self.label[ancestor];
+            }
+            self.ancestor[below] = self.ancestor[ancestor];
+        }
+        path.clear();
+        self.path = path;
+        self.label[idx]
+    }
+}
+
+/// The tiles next to the one at `idx`, with the bit of the direction they
+/// are in, see [`Walks::sides`].
+fn neighbours(grid: &Grid, idx: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (0..DIRECTIONS.len()).filter_map(move |bit| Some((bit, neighbour(grid, idx, bit)?)))
+}
+
+/// The tile next to the one at `idx`, in the direction of `bit`.
+fn neighbour(grid: &Grid, idx: usize, bit: usize) -> Option<usize> {
+    let width = grid.width() as usize;
+    match DIRECTIONS[bit] {
+        Direction::Left => (!idx.is_multiple_of(width)).then(|| idx - 1),
+        Direction::Right => (idx % width + 1 < width).then_some(idx + 1),
+        Direction::Up => idx.checked_sub(width),
+        Direction::Down => Some(idx + width).filter(|next| *next < grid.tiles().len()),
+    }
+}
+
+/// Run the game without input until Bobby stands still, counting the steps.
+fn settle(game: &mut GameState, mut events: Vec<GameEvent>) -> (Outcome, u32) {
+    let mut steps = 0;
+    let max_frames = MAX_FRAMES_PER_TILE * game.map_info().data.tiles().len() as u32;
+    for _ in 0..max_frames {
+        for event in events.drain(..) {
+            match event {
+                GameEvent::Moved { .. } => steps += 1,
+                GameEvent::Finished | GameEvent::LevelCleared => return (Outcome::Finished, steps),
+                GameEvent::Died | GameEvent::Respawned => return (Outcome::Dead, steps),
+                _ => {}
+            }
+        }
+        if game.is_finished() && game.current_tile() == Tile::Finish {
+            return (Outcome::Finished, steps);
+        }
+        if game.is_at_rest() {
+            return (Outcome::Alive, steps);
+        }
+        events = game.step(None);
+    }
+    (Outcome::Dead, steps)
+}
+
+fn moves_to(nodes: &[Node], mut index: usize) -> Vec<Direction> {
+    let mut moves = Vec::new();
+    while index != NO_PARENT {
+        moves.push(nodes[index].direction);
+        index = nodes[index].parent;
+    }
+    moves.reverse();
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Map, MapInfo};
+
+    /// A level of one or more rows of [`Tile::token`]s.
+    fn level(rows: &[&str]) -> GameState {
+        let width = rows[0].split_whitespace().count() as u32;
+        let tiles = rows
+            .iter()
+            .flat_map(|row| row.split_whitespace())
+            .map(|token| Tile::from_token(token).expect(token))
+            .collect();
+        GameState::new(MapInfo::from_tiles(Grid::from_tiles(
+            width,
+            rows.len() as u32,
+            tiles,
+        )))
+    }
+
+    /// Whether playing `moves` from the start of `game` finishes it.
+    fn finishes(game: &GameState, moves: &[Direction]) -> bool {
+        let mut game = game.clone();
+        let mut outcome = settle(&mut game, Vec::new()).0;
+        for direction in moves {
+            assert!(matches!(outcome, Outcome::Alive));
+            let events = game.step(Some(*direction));
+            outcome = settle(&mut game, events).0;
+        }
+        matches!(outcome, Outcome::Finished)
+    }
+
+    #[test]
+    fn solves_a_small_level() {
+        // the crumbling floor is the only way down, and can be crossed once
+        let game = level(&["St .. ca", "#0 cr #0", "ca .. Fi"]);
+        let Solution::Solved(moves) = solve(&game) else {
+            panic!("no solution");
+        };
+        assert!(finishes(&game, &moves));
+        assert_eq!(moves.len(), 8);
+    }
+
+    #[test]
+    fn finds_the_shortest_moves() {
+        // going for the nearest carrots first takes 23 moves
+        let game = level(&[
+            "ca #0 .. .. #0 ..",
+            ".. .. ca #0 .. ca",
+            ".. .. #0 .. .. ..",
+            "ca .. ca .. St Fi",
+        ]);
+        let Solution::Solved(moves) = solve(&game) else {
+            panic!("no solution");
+        };
+        assert!(finishes(&game, &moves));
+        assert_eq!(moves.len(), 21);
+    }
+
+    #[test]
+    fn a_crumbling_floor_crossed_twice_is_unsolvable() {
+        let game = level(&["ca cr St .. Fi"]);
+        assert!(matches!(solve(&game), Solution::Unsolvable { .. }));
+    }
+
+    #[test]
+    fn a_cancelled_search_gives_up() {
+        let game = level(&["St .. ca", "#0 cr #0", "ca .. Fi"]);
+        assert_eq!(
+            solve_cancellable(&game, usize::MAX, &AtomicBool::new(true)),
+            None
+        );
+    }
+
+    #[test]
+    fn solves_the_first_levels() {
+        for map in (1..=3).map(Map::Normal).chain((1..=3).map(Map::Egg)) {
+            let game = GameState::new(map.load_map_info().unwrap());
+            let Some(Solution::Solved(moves)) = solve_with_limit(&game, 20_000) else {
+                panic!("{map:?} not solved");
+            };
+            assert!(finishes(&game, &moves), "{map:?}");
+        }
+    }
+}
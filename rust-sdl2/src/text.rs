@@ -0,0 +1,137 @@
+//! A text form of the `.blm` levels that can be reviewed in a diff.
+//!
+//! The first line keeps the header bytes in hex, followed by one row per
+//! line with one [`Tile::token`] per tile:
+//!
+//! ```text
+//! header 00 00 01 00
+//! #0 #1 .. ca ca .. #1 ...
+//! ```
+//!
+//! The header of a level of another size than 16x16 ends with its width and
+//! height, such as `header 00 00 02 00 14 10` for 20x16 tiles.
+
+use crate::map::blm_size;
+use crate::tile::Tile;
+
+const HEADER_KEYWORD: &str = "header";
+
+/// Whether the content of a level file is in the text form.
+pub fn is_text(data: &[u8]) -> bool {
+    data.starts_with(HEADER_KEYWORD.as_bytes())
+}
+
+pub fn blm_to_text(data: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let (width, height, header_len) = blm_size(data)?;
+    let size = header_len + (width * height) as usize;
+    if data.len() != size {
+        return Err(format!(
+            "Invalid level size: expected {} bytes, got {}",
+            size,
+            data.len()
+        )
+        .into());
+    }
+    let (header, body) = data.split_at(header_len);
+    let mut text = String::from(HEADER_KEYWORD);
+    for byte in header {
+        text.push_str(&format!(" {:02x}", byte));
+    }
+    text.push('\n');
+    for (y, row) in body.chunks(width as usize).enumerate() {
+        let tokens = row
+            .iter()
+            .enumerate()
+            .map(|(x, byte)| {
+                Tile::from_byte(*byte)
+                    .map(Tile::token)
+                    .ok_or_else(|| format!("Invalid tile {} at ({}, {})", byte, x, y))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        text.push_str(&tokens.join(" "));
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+pub fn text_to_blm(text: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let (header_idx, header_line) = lines.next().ok_or("Empty level")?;
+    let header = header_line
+        .strip_prefix(HEADER_KEYWORD)
+        .ok_or_else(|| {
+            format!(
+                "line {}: Expected `{HEADER_KEYWORD}`, got: {header_line}",
+                header_idx + 1
+            )
+        })?
+        .split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("line {}: Invalid header: {err}", header_idx + 1))?;
+    let (width, height, header_len) =
+        blm_size(&header).map_err(|err| format!("line {}: {err}", header_idx + 1))?;
+    if header.len() != header_len {
+        return Err(format!(
+            "line {}: Expected {} header bytes, got {}",
+            header_idx + 1,
+            header_len,
+            header.len()
+        )
+        .into());
+    }
+
+    let mut data = header;
+    let mut rows = 0;
+    for (idx, line) in lines {
+        if rows == height {
+            return Err(format!("line {}: Expected {} rows", idx + 1, height).into());
+        }
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        if tokens.len() != width as usize {
+            return Err(format!(
+                "line {}: Expected {} tiles, got {}",
+                idx + 1,
+                width,
+                tokens.len()
+            )
+            .into());
+        }
+        for token in tokens {
+            let tile = Tile::from_token(token)
+                .ok_or_else(|| format!("line {}: Invalid tile: {}", idx + 1, token))?;
+            data.push(tile.to_byte());
+        }
+        rows += 1;
+    }
+    if rows != height {
+        return Err(format!("Expected {} rows, got {}", height, rows).into());
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::map::{EGG_LEVELS, NORMAL_LEVELS};
+
+    #[test]
+    fn bundled_levels_round_trip() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/assets/level");
+        let files = (1..=NORMAL_LEVELS)
+            .map(|num| format!("normal{num:02}.blm"))
+            .chain((1..=EGG_LEVELS).map(|num| format!("egg{num:02}.blm")));
+        for file in files {
+            let blm = fs::read(dir.join(&file)).unwrap();
+            let text = blm_to_text(&blm).unwrap();
+            assert!(is_text(text.as_bytes()), "{file}");
+            assert_eq!(text_to_blm(&text).unwrap(), blm, "{file}");
+        }
+    }
+}
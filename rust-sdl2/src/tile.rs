@@ -0,0 +1,369 @@
+use crate::game::{Direction, KeyColor};
+
+/// One cell of a level, as stored in the `.blm` files.
+///
+/// The byte value of a tile is also its index in `tileset.png`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Tile {
+    /// Grass, trees, fences and the like, nobody can walk on them (0..=17).
+    Wall(u8),
+    Floor,
+    Carrot,
+    /// What is left after a carrot is eaten.
+    CarrotHole,
+    Start,
+    RedSwitch,
+    RedSwitchToggled,
+    /// Rail corner, open to the right and the bottom.
+    RailRightDown,
+    RailLeftDown,
+    RailLeftUp,
+    RailRightUp,
+    RailHorizontal,
+    RailVertical,
+    /// Breaks into a [`Tile::Hole`] once Bobby steps off it.
+    Crumble,
+    Hole,
+    KeyGray,
+    LockGray,
+    KeyYellow,
+    LockYellow,
+    KeyRed,
+    LockRed,
+    YellowSwitch,
+    YellowSwitchToggled,
+    ConveyorLeft,
+    ConveyorRight,
+    ConveyorUp,
+    ConveyorDown,
+    Finish,
+    Egg,
+    /// An egg tile Bobby already walked over, it is blocked from now on.
+    EggPlaced,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Collectible {
+    Carrot,
+    Egg,
+    Key(KeyColor),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SwitchColor {
+    Red,
+    Yellow,
+}
+
+impl Tile {
+    pub fn from_byte(byte: u8) -> Option<Tile> {
+        let tile = match byte {
+            0..=17 => Tile::Wall(byte),
+            18 => Tile::Floor,
+            19 => Tile::Carrot,
+            20 => Tile::CarrotHole,
+            21 => Tile::Start,
+            22 => Tile::RedSwitch,
+            23 => Tile::RedSwitchToggled,
+            24 => Tile::RailRightDown,
+            25 => Tile::RailLeftDown,
+            26 => Tile::RailLeftUp,
+            27 => Tile::RailRightUp,
+            28 => Tile::RailHorizontal,
+            29 => Tile::RailVertical,
+            30 => Tile::Crumble,
+            31 => Tile::Hole,
+            32 => Tile::KeyGray,
+            33 => Tile::LockGray,
+            34 => Tile::KeyYellow,
+            35 => Tile::LockYellow,
+            36 => Tile::KeyRed,
+            37 => Tile::LockRed,
+            38 => Tile::YellowSwitch,
+            39 => Tile::YellowSwitchToggled,
+            40 => Tile::ConveyorLeft,
+            41 => Tile::ConveyorRight,
+            42 => Tile::ConveyorUp,
+            43 => Tile::ConveyorDown,
+            44 => Tile::Finish,
+            45 => Tile::Egg,
+            46 => Tile::EggPlaced,
+            _ => return None,
+        };
+        Some(tile)
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Tile::Wall(byte) => byte,
+            Tile::Floor => 18,
+            Tile::Carrot => 19,
+            Tile::CarrotHole => 20,
+            Tile::Start => 21,
+            Tile::RedSwitch => 22,
+            Tile::RedSwitchToggled => 23,
+            Tile::RailRightDown => 24,
+            Tile::RailLeftDown => 25,
+            Tile::RailLeftUp => 26,
+            Tile::RailRightUp => 27,
+            Tile::RailHorizontal => 28,
+            Tile::RailVertical => 29,
+            Tile::Crumble => 30,
+            Tile::Hole => 31,
+            Tile::KeyGray => 32,
+            Tile::LockGray => 33,
+            Tile::KeyYellow => 34,
+            Tile::LockYellow => 35,
+            Tile::KeyRed => 36,
+            Tile::LockRed => 37,
+            Tile::YellowSwitch => 38,
+            Tile::YellowSwitchToggled => 39,
+            Tile::ConveyorLeft => 40,
+            Tile::ConveyorRight => 41,
+            Tile::ConveyorUp => 42,
+            Tile::ConveyorDown => 43,
+            Tile::Finish => 44,
+            Tile::Egg => 45,
+            Tile::EggPlaced => 46,
+        }
+    }
+
+    /// The two characters standing for this tile in text levels.
+    pub fn token(self) -> &'static str {
+        const WALLS: [&str; 18] = [
+            "#0", "#1", "#2", "#3", "#4", "#5", "#6", "#7", "#8", "#9", "#a", "#b", "#c", "#d",
+            "#e", "#f", "#g", "#h",
+        ];
+        match self {
+            Tile::Wall(byte) => WALLS[byte as usize],
+            Tile::Floor => "..",
+            Tile::Carrot => "ca",
+            Tile::CarrotHole => "ch",
+            Tile::Start => "St",
+            Tile::RedSwitch => "r0",
+            Tile::RedSwitchToggled => "r1",
+            Tile::RailRightDown => "RD",
+            Tile::RailLeftDown => "LD",
+            Tile::RailLeftUp => "LU",
+            Tile::RailRightUp => "RU",
+            Tile::RailHorizontal => "--",
+            Tile::RailVertical => "||",
+            Tile::Crumble => "cr",
+            Tile::Hole => "oo",
+            Tile::KeyGray => "kg",
+            Tile::LockGray => "Lg",
+            Tile::KeyYellow => "ky",
+            Tile::LockYellow => "Ly",
+            Tile::KeyRed => "kr",
+            Tile::LockRed => "Lr",
+            Tile::YellowSwitch => "y0",
+            Tile::YellowSwitchToggled => "y1",
+            Tile::ConveyorLeft => "<<",
+            Tile::ConveyorRight => ">>",
+            Tile::ConveyorUp => "^^",
+            Tile::ConveyorDown => "vv",
+            Tile::Finish => "Fi",
+            Tile::Egg => "eg",
+            Tile::EggPlaced => "ep",
+        }
+    }
+
+    /// What the tile is called in the level editor.
+    pub fn name(self) -> &'static str {
+        const WALLS: [&str; 18] = [
+            "Wall 0", "Wall 1", "Wall 2", "Wall 3", "Wall 4", "Wall 5", "Wall 6", "Wall 7",
+            "Wall 8", "Wall 9", "Wall 10", "Wall 11", "Wall 12", "Wall 13", "Wall 14", "Wall 15",
+            "Wall 16", "Wall 17",
+        ];
+        match self {
+            Tile::Wall(byte) => WALLS[byte as usize],
+            Tile::Floor => "Floor",
+            Tile::Carrot => "Carrot",
+            Tile::CarrotHole => "Carrot hole",
+            Tile::Start => "Start",
+            Tile::RedSwitch => "Red switch",
+            Tile::RedSwitchToggled => "Red switch on",
+            Tile::RailRightDown => "Rail right-down",
+            Tile::RailLeftDown => "Rail left-down",
+            Tile::RailLeftUp => "Rail left-up",
+            Tile::RailRightUp => "Rail right-up",
+            Tile::RailHorizontal => "Rail horizontal",
+            Tile::RailVertical => "Rail vertical",
+            Tile::Crumble => "Crumbling floor",
+            Tile::Hole => "Hole",
+            Tile::KeyGray => "Gray key",
+            Tile::LockGray => "Gray lock",
+            Tile::KeyYellow => "Yellow key",
+            Tile::LockYellow => "Yellow lock",
+            Tile::KeyRed => "Red key",
+            Tile::LockRed => "Red lock",
+            Tile::YellowSwitch => "Yellow switch",
+            Tile::YellowSwitchToggled => "Yellow switch on",
+            Tile::ConveyorLeft => "Conveyor left",
+            Tile::ConveyorRight => "Conveyor right",
+            Tile::ConveyorUp => "Conveyor up",
+            Tile::ConveyorDown => "Conveyor down",
+            Tile::Finish => "Finish",
+            Tile::Egg => "Egg",
+            Tile::EggPlaced => "Egg placed",
+        }
+    }
+
+    pub fn from_token(token: &str) -> Option<Tile> {
+        (0..=u8::MAX)
+            .map_while(Tile::from_byte)
+            .find(|tile| tile.token() == token)
+    }
+
+    /// Index of the sprite in `tileset.png` (8 sprites per row).
+    pub fn sprite_index(self) -> u8 {
+        self.to_byte()
+    }
+
+    /// The two sides a rail or a conveyor can be passed through.
+    fn sides(self) -> Option<(Direction, Direction)> {
+        let sides = match self {
+            Tile::RailRightDown => (Direction::Right, Direction::Down),
+            Tile::RailLeftDown => (Direction::Left, Direction::Down),
+            Tile::RailLeftUp => (Direction::Left, Direction::Up),
+            Tile::RailRightUp => (Direction::Right, Direction::Up),
+            Tile::RailHorizontal | Tile::ConveyorLeft | Tile::ConveyorRight => {
+                (Direction::Left, Direction::Right)
+            }
+            Tile::RailVertical | Tile::ConveyorUp | Tile::ConveyorDown => {
+                (Direction::Up, Direction::Down)
+            }
+            _ => return None,
+        };
+        Some(sides)
+    }
+
+    /// Whether Bobby may walk onto this tile while moving in `direction`.
+    ///
+    /// Locks are enterable here, having the matching key is up to the caller.
+    pub fn can_enter(self, direction: Direction) -> bool {
+        match self {
+            Tile::Wall(_) | Tile::EggPlaced => false,
+            _ => self.can_pass(direction.opposite(), direction),
+        }
+    }
+
+    /// Whether Bobby may walk off this tile while moving in `direction`.
+    pub fn can_leave(self, direction: Direction) -> bool {
+        self.can_pass(direction, direction)
+    }
+
+    fn can_pass(self, side: Direction, direction: Direction) -> bool {
+        match self.sides() {
+            Some((a, b)) if side != a && side != b => false,
+            _ => self
+                .conveyor()
+                .is_none_or(|flow| direction != flow.opposite()),
+        }
+    }
+
+    pub fn collectible(self) -> Option<Collectible> {
+        match self {
+            Tile::Carrot => Some(Collectible::Carrot),
+            Tile::Egg => Some(Collectible::Egg),
+            Tile::KeyGray => Some(Collectible::Key(KeyColor::Gray)),
+            Tile::KeyYellow => Some(Collectible::Key(KeyColor::Yellow)),
+            Tile::KeyRed => Some(Collectible::Key(KeyColor::Red)),
+            _ => None,
+        }
+    }
+
+    /// The tile left behind once the collectible on it is taken.
+    pub fn collected(self) -> Tile {
+        match self {
+            Tile::Carrot => Tile::CarrotHole,
+            Tile::Egg => Tile::EggPlaced,
+            Tile::KeyGray | Tile::KeyYellow | Tile::KeyRed => Tile::Floor,
+            tile => tile,
+        }
+    }
+
+    pub fn lock(self) -> Option<KeyColor> {
+        match self {
+            Tile::LockGray => Some(KeyColor::Gray),
+            Tile::LockYellow => Some(KeyColor::Yellow),
+            Tile::LockRed => Some(KeyColor::Red),
+            _ => None,
+        }
+    }
+
+    /// The direction a conveyor pushes Bobby to.
+    pub fn conveyor(self) -> Option<Direction> {
+        match self {
+            Tile::ConveyorLeft => Some(Direction::Left),
+            Tile::ConveyorRight => Some(Direction::Right),
+            Tile::ConveyorUp => Some(Direction::Up),
+            Tile::ConveyorDown => Some(Direction::Down),
+            _ => None,
+        }
+    }
+
+    /// Rails turn on their own every time Bobby walks off them.
+    pub fn is_rail(self) -> bool {
+        matches!(
+            self,
+            Tile::RailRightDown
+                | Tile::RailLeftDown
+                | Tile::RailLeftUp
+                | Tile::RailRightUp
+                | Tile::RailHorizontal
+                | Tile::RailVertical
+        )
+    }
+
+    /// The color of the switch Bobby toggles by stepping on this tile. A
+    /// toggled switch does nothing until one of its color is stepped on.
+    pub fn switch(self) -> Option<SwitchColor> {
+        match self {
+            Tile::RedSwitch => Some(SwitchColor::Red),
+            Tile::YellowSwitch => Some(SwitchColor::Yellow),
+            _ => None,
+        }
+    }
+
+    /// The switch this tile reacts to: red switches turn rails, yellow
+    /// switches reverse conveyors.
+    pub fn switch_group(self) -> Option<SwitchColor> {
+        if self.is_rail() {
+            Some(SwitchColor::Red)
+        } else if self.conveyor().is_some() {
+            Some(SwitchColor::Yellow)
+        } else {
+            match self {
+                Tile::RedSwitch | Tile::RedSwitchToggled => Some(SwitchColor::Red),
+                Tile::YellowSwitch | Tile::YellowSwitchToggled => Some(SwitchColor::Yellow),
+                _ => None,
+            }
+        }
+    }
+
+    /// The tile after its switch group is toggled once.
+    pub fn toggled(self) -> Tile {
+        match self {
+            Tile::RedSwitch => Tile::RedSwitchToggled,
+            Tile::RedSwitchToggled => Tile::RedSwitch,
+            // right angle
+            Tile::RailRightDown => Tile::RailLeftDown,
+            Tile::RailLeftDown => Tile::RailLeftUp,
+            Tile::RailLeftUp => Tile::RailRightUp,
+            Tile::RailRightUp => Tile::RailRightDown,
+            // line
+            Tile::RailHorizontal => Tile::RailVertical,
+            Tile::RailVertical => Tile::RailHorizontal,
+            Tile::YellowSwitch => Tile::YellowSwitchToggled,
+            Tile::YellowSwitchToggled => Tile::YellowSwitch,
+            // left / right
+            Tile::ConveyorLeft => Tile::ConveyorRight,
+            Tile::ConveyorRight => Tile::ConveyorLeft,
+            // up / down
+            Tile::ConveyorUp => Tile::ConveyorDown,
+            Tile::ConveyorDown => Tile::ConveyorUp,
+            tile => tile,
+        }
+    }
+}
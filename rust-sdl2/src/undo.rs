@@ -0,0 +1,204 @@
+use crate::bobby::Bobby;
+use crate::game::{GameEvent, GameState};
+use crate::grid::Grid;
+
+/// The level and Bobby's counters while he stands still on a tile.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub(crate) data: Grid,
+    pub(crate) bobby: Bobby,
+}
+
+/// The tiles Bobby stood still on since the level started, to walk back the
+/// moves one at a time.
+///
+/// A move lasts until Bobby stands still again, so a ride on conveyors is
+/// undone at once, and so is a death.
+#[derive(Debug, Clone, Default)]
+pub struct UndoStack {
+    snapshots: Vec<Snapshot>,
+    /// Bobby moved since the last snapshot.
+    moved: bool,
+    undos: usize,
+}
+
+impl UndoStack {
+    pub fn new() -> UndoStack {
+        UndoStack {
+            snapshots: Vec::new(),
+            // the start tile is saved once the fade in is over
+            moved: true,
+            undos: 0,
+        }
+    }
+
+    /// Call after every [`GameState::step`] with its events, to save the
+    /// state each time Bobby comes to rest.
+    pub fn update(&mut self, game: &GameState, events: &[GameEvent]) {
+        // a move is only over once Bobby stands still, undoing it before goes
+        // back to where it started
+        if !game.is_at_rest()
+            || events
+                .iter()
+                .any(|event| matches!(event, GameEvent::Moved { .. } | GameEvent::Respawned))
+        {
+            self.moved = true;
+        }
+        if self.moved && game.is_at_rest() {
+            self.snapshots.push(game.snapshot());
+            self.moved = false;
+        }
+    }
+
+    /// Go back to the tile the last move started from. Returns whether there
+    /// was a move to undo.
+    pub fn undo(&mut self, game: &mut GameState) -> bool {
+        if !self.moved {
+            if self.snapshots.len() < 2 {
+                return false;
+            }
+            self.snapshots.pop();
+        }
+        let Some(snapshot) = self.snapshots.last() else {
+            return false;
+        };
+        game.restore(snapshot);
+        self.moved = false;
+        self.undos += 1;
+        true
+    }
+
+    /// How many moves were undone, a clear time with undos doesn't compare
+    /// with one without.
+    pub fn undos(&self) -> usize {
+        self.undos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Direction;
+    use crate::map::MapInfo;
+    use crate::tile::Tile;
+
+    /// A level of one row of [`Tile::token`]s, with the stack saving it
+    /// since the start of the fade in.
+    fn level(row: &str) -> (GameState, UndoStack) {
+        let tiles = row
+            .split_whitespace()
+            .map(|token| Tile::from_token(token).expect(token))
+            .collect::<Vec<_>>();
+        let grid = Grid::from_tiles(tiles.len() as u32, 1, tiles);
+        let mut game = GameState::new(MapInfo::from_tiles(grid));
+        let mut undo = UndoStack::new();
+        settle(&mut game, &mut undo);
+        (game, undo)
+    }
+
+    /// Run without input until Bobby stands still.
+    fn settle(game: &mut GameState, undo: &mut UndoStack) {
+        for _ in 0..1000 {
+            if game.is_at_rest() {
+                return;
+            }
+            let events = game.step(None);
+            undo.update(game, &events);
+        }
+        panic!("Bobby never stands still");
+    }
+
+    /// Press `direction` once, then wait for everything it causes.
+    fn walk(game: &mut GameState, undo: &mut UndoStack, direction: Direction) {
+        let events = game.step(Some(direction));
+        undo.update(game, &events);
+        settle(game, undo);
+    }
+
+    fn tile(game: &GameState, x: u32) -> Tile {
+        game.map_info().data[(x, 0)]
+    }
+
+    #[test]
+    fn snapshots_are_taken_at_rest() {
+        let (mut game, mut undo) = level("St .. ..");
+        assert_eq!(undo.snapshots.len(), 1);
+        let events = game.step(Some(Direction::Right));
+        undo.update(&game, &events);
+        assert!(!game.is_at_rest());
+        assert_eq!(undo.snapshots.len(), 1);
+        settle(&mut game, &mut undo);
+        assert_eq!(undo.snapshots.len(), 2);
+        // standing still or walking into the edge isn't a move
+        for _ in 0..10 {
+            let events = game.step(None);
+            undo.update(&game, &events);
+        }
+        walk(&mut game, &mut undo, Direction::Up);
+        assert_eq!(undo.snapshots.len(), 2);
+    }
+
+    #[test]
+    fn undo_restores_the_tiles_and_the_counters() {
+        let (mut game, mut undo) = level("kr St eg ca cr .. r0 --");
+        walk(&mut game, &mut undo, Direction::Left);
+        assert_eq!(game.bobby().key_red, 1);
+        for _ in 0..6 {
+            walk(&mut game, &mut undo, Direction::Right);
+        }
+        assert_eq!(game.bobby().coord_src, (6, 0));
+        assert_eq!(tile(&game, 2).to_byte(), Tile::EggPlaced.to_byte());
+        assert_eq!(tile(&game, 3), Tile::CarrotHole);
+        assert_eq!(tile(&game, 4).to_byte(), 31);
+        assert_eq!(tile(&game, 6), Tile::RedSwitchToggled);
+        assert_eq!(tile(&game, 7), Tile::RailVertical);
+        assert_eq!((game.bobby().carrot_count, game.bobby().egg_count), (1, 1));
+
+        // the red switch
+        assert!(undo.undo(&mut game));
+        assert_eq!(game.bobby().coord_src, (5, 0));
+        assert_eq!(tile(&game, 6), Tile::RedSwitch);
+        assert_eq!(tile(&game, 7), Tile::RailHorizontal);
+        // the crumbling floor
+        assert!(undo.undo(&mut game));
+        assert_eq!(game.bobby().coord_src, (4, 0));
+        assert_eq!(tile(&game, 4).to_byte(), 30);
+        // the carrot and the egg
+        assert!(undo.undo(&mut game));
+        assert!(undo.undo(&mut game));
+        assert_eq!(game.bobby().coord_src, (2, 0));
+        assert_eq!(tile(&game, 2), Tile::Egg);
+        assert_eq!(tile(&game, 3), Tile::Carrot);
+        assert_eq!((game.bobby().carrot_count, game.bobby().egg_count), (0, 0));
+        assert_eq!(game.bobby().key_red, 1);
+        // the key
+        assert!(undo.undo(&mut game));
+        assert!(undo.undo(&mut game));
+        assert!(undo.undo(&mut game));
+        assert_eq!(game.bobby().coord_src, (1, 0));
+        assert_eq!(tile(&game, 0), Tile::KeyRed);
+        assert_eq!(game.bobby().key_red, 0);
+        assert!(!undo.undo(&mut game));
+    }
+
+    #[test]
+    fn undos_are_counted() {
+        let (mut game, mut undo) = level("St .. ..");
+        assert!(!undo.undo(&mut game));
+        assert_eq!(undo.undos(), 0);
+        walk(&mut game, &mut undo, Direction::Right);
+        walk(&mut game, &mut undo, Direction::Right);
+        assert!(undo.undo(&mut game));
+        assert_eq!(undo.undos(), 1);
+        // a move started is undone back to where it started
+        let events = game.step(Some(Direction::Right));
+        undo.update(&game, &events);
+        assert!(undo.undo(&mut game));
+        assert_eq!(game.bobby().coord_src, (1, 0));
+        assert_eq!(undo.undos(), 2);
+        assert!(undo.undo(&mut game));
+        assert!(!undo.undo(&mut game));
+        assert_eq!(undo.undos(), 3);
+        assert_eq!(game.bobby().coord_src, (0, 0));
+    }
+}
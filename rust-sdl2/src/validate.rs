@@ -0,0 +1,215 @@
+use std::fmt;
+
+use crate::game::Direction;
+use crate::grid::Grid;
+use crate::map::MapInfo;
+use crate::tile::Tile;
+
+/// Something wrong with a level, found by [`validate`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Problem {
+    pub coord: Option<(u32, u32)>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.coord {
+            Some((x, y)) => write!(f, "({}, {}): {}", x, y, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Lint a level for mistakes `MapInfo::from_blm` silently accepts.
+pub fn validate(map_info: &MapInfo) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let coord = |idx: usize| map_info.data.coord(idx);
+    let find = |tile: Tile| {
+        map_info
+            .data
+            .tiles()
+            .iter()
+            .enumerate()
+            .filter(move |(_, t)| **t == tile)
+            .map(|(idx, _)| idx)
+    };
+
+    let starts = find(Tile::Start).collect::<Vec<_>>();
+    match starts.len() {
+        0 => problems.push(Problem {
+            coord: None,
+            message: "No start tile".to_string(),
+        }),
+        1 => {}
+        _ => {
+            for idx in &starts {
+                problems.push(Problem {
+                    coord: Some(coord(*idx)),
+                    message: format!("One of {} start tiles, the last one wins", starts.len()),
+                });
+            }
+        }
+    }
+    if find(Tile::Finish).next().is_none() {
+        problems.push(Problem {
+            coord: None,
+            message: "No finish tile".to_string(),
+        });
+    }
+    if map_info.carrot_total == 0 && map_info.egg_total == 0 {
+        problems.push(Problem {
+            coord: None,
+            message: "No carrot and no egg".to_string(),
+        });
+    }
+
+    if let Some(start) = starts.last() {
+        let reachable = reachable_from(&map_info.data, *start);
+        for (idx, tile) in map_info.data.tiles().iter().enumerate() {
+            let what = match tile {
+                Tile::Carrot => "carrot",
+                Tile::Egg => "egg",
+                Tile::Finish => "finish tile",
+                Tile::KeyGray | Tile::KeyYellow | Tile::KeyRed => "key",
+                _ => continue,
+            };
+            if !reachable[idx] {
+                problems.push(Problem {
+                    coord: Some(coord(idx)),
+                    message: format!("Unreachable {what}"),
+                });
+            }
+        }
+    }
+    problems
+}
+
+/// Flood fill from `start` over everything that is not a wall.
+///
+/// Rails, conveyors, locks and switches are all considered passable, so this
+/// never reports a tile that the game could reach, but it may miss some that
+/// it can't.
+fn reachable_from(data: &Grid, start: usize) -> Vec<bool> {
+    let mut reachable = vec![false; data.tiles().len()];
+    let mut stack = vec![start];
+    reachable[start] = true;
+    while let Some(idx) = stack.pop() {
+        for direction in [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ] {
+            let Some(coord) = data.neighbour(data.coord(idx), direction) else {
+                continue;
+            };
+            let next = data.index(coord);
+            let passable = !matches!(data[coord], Tile::Wall(_) | Tile::EggPlaced | Tile::Hole);
+            if passable && !reachable[next] {
+                reachable[next] = true;
+                stack.push(next);
+            }
+        }
+    }
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A level of one or more rows of [`Tile::token`]s.
+    fn level(rows: &[&str]) -> MapInfo {
+        let width = rows[0].split_whitespace().count() as u32;
+        let tiles = rows
+            .iter()
+            .flat_map(|row| row.split_whitespace())
+            .map(|token| Tile::from_token(token).expect(token))
+            .collect();
+        MapInfo::from_tiles(Grid::from_tiles(width, rows.len() as u32, tiles))
+    }
+
+    fn messages(problems: &[Problem]) -> Vec<&str> {
+        problems
+            .iter()
+            .map(|problem| problem.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn a_valid_level_has_no_problem() {
+        assert_eq!(validate(&level(&["St ca .. Fi"])), []);
+    }
+
+    #[test]
+    fn a_level_needs_a_start() {
+        assert_eq!(
+            validate(&level(&[".. ca .. Fi"])),
+            [Problem {
+                coord: None,
+                message: "No start tile".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn every_start_but_one_is_reported() {
+        let problems = validate(&level(&["St ca .. Fi", ".. .. St .."]));
+        let coords = problems
+            .iter()
+            .map(|problem| problem.coord)
+            .collect::<Vec<_>>();
+        assert_eq!(coords, [Some((0, 0)), Some((2, 1))]);
+        assert_eq!(
+            messages(&problems),
+            [
+                "One of 2 start tiles, the last one wins",
+                "One of 2 start tiles, the last one wins",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_level_needs_a_finish() {
+        assert_eq!(
+            messages(&validate(&level(&["St ca .."]))),
+            ["No finish tile"]
+        );
+    }
+
+    #[test]
+    fn a_level_needs_carrots_or_eggs() {
+        assert_eq!(
+            messages(&validate(&level(&["St .. .. Fi"]))),
+            ["No carrot and no egg"]
+        );
+    }
+
+    #[test]
+    fn tiles_behind_walls_are_unreachable() {
+        let problems = validate(&level(&[
+            "St ca #0 ca",
+            ".. .. #0 eg",
+            "#0 #0 #0 ..",
+            ".. Fi #0 ..",
+        ]));
+        assert_eq!(
+            problems,
+            [
+                Problem {
+                    coord: Some((3, 0)),
+                    message: "Unreachable carrot".to_string(),
+                },
+                Problem {
+                    coord: Some((3, 1)),
+                    message: "Unreachable egg".to_string(),
+                },
+                Problem {
+                    coord: Some((1, 3)),
+                    message: "Unreachable finish tile".to_string(),
+                },
+            ]
+        );
+    }
+}